@@ -1,25 +1,42 @@
-//! Docker, 可以使用第三方库 `bollard`
+//! Docker, 默认使用 docker 命令行, 开启 `bollard` feature 后可以使用 `exec_with_bollard`
 
 use crate::error::Error;
-use crate::DockerConfig;
+use crate::event::BuildEvent;
+use crate::{DockerConfig, DockerResult, KubernetesStrategy};
 use handlers::command::func::CommandFuncHandler;
 use handlers::command::CommandHandler;
 use handlers::file::FileHandler;
 use handlers::utils::Utils;
-use log::{error, info};
+use log::{error, info, warn};
 use sftp::sftp::SftpHandler;
 use ssh2::Session;
 use std::io::Read;
 use std::path::Path;
 use std::sync::{Arc, Mutex, RwLock};
 
+#[cfg(feature = "bollard")]
+mod bollard_api;
+#[cfg(feature = "kube")]
+mod kube_api;
+
 pub struct DockerHandler;
 
+const LAST_LOG_LINES: usize = 20; // 命令失败时错误信息中包含的最后输出行数
+const DEFAULT_ROLLOUT_TIMEOUT: u64 = 300; // 默认等待 rollout 完成的时间(秒)
+
 impl DockerHandler {
-    pub async fn exec<F>(docker_config: &DockerConfig, server: &sftp::config::Server, func: F) -> Result<bool, String>
+    pub async fn exec<F>(docker_config: &DockerConfig, server: &sftp::config::Server, func: F) -> Result<DockerResult, String>
     where
         F: Fn(&str) + Send + Sync + 'static,
     {
+        // 收集所有输出日志
+        let logs: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+        let logs_cloned = logs.clone();
+        let func = move |msg: &str| {
+            logs_cloned.lock().unwrap().push(msg.to_string());
+            func(msg);
+        };
+
         let msg = format!("docker config: {:#?}", docker_config);
         func(&msg);
 
@@ -43,39 +60,8 @@ impl DockerHandler {
         let mut commands: Vec<String> = Vec::new();
         commands.push(format!("cd {}", docker_config.dir));
 
-        let time = Utils::get_date(Some("%Y%m%d%H%M%S".to_string()));
-
-        // 创建 nginx.conf 文件
-        let nginx_file_name = format!("nginx_{}.conf", time); // nginx 文件名
-        let nginx_file_path = Path::new(&docker_config.dir).join(&nginx_file_name);
-        let nginx_file_path_str = nginx_file_path.to_string_lossy().to_string();
-        FileHandler::write_to_file_when_clear(&nginx_file_path_str, &docker_config.nginx_content)?;
-
-        let mut dockerfile_content = docker_config.dockerfile.clone();
-
-        // 添加 nginx
-        if !docker_config.nginx_path.is_empty() && !docker_config.nginx_content.is_empty() {
-            let mut content: Vec<String> = dockerfile_content.lines().map(String::from).collect();
-            content.push(format!("ADD {} {}", nginx_file_name, docker_config.nginx_path));
-
-            if !docker_config.deploy_dir.is_empty() {
-                content.push(format!("COPY {} ./", docker_config.deploy_dir));
-            }
-
-            dockerfile_content = content.join("\n");
-        }
-
-        dockerfile_content.push_str("\n CMD [\"nginx\", \"-g\", \"daemon off;\"]");
-
-        // 创建 Dockerfile 文件
-        let dockerfile_file_name = format!("Dockerfile_{}", time); // dockerfile 文件名
-        let dockerfile_file_path = Path::new(&docker_config.dir).join(&dockerfile_file_name);
-        let dockerfile_file_path_str = dockerfile_file_path.to_string_lossy().to_string();
-        FileHandler::write_to_file_when_clear(&dockerfile_file_path_str, &dockerfile_content)?;
-        let msg = format!("Dockerfile content: \n{:#?}", dockerfile_content);
-        func(&msg);
-
-        let image = format!("{}/{}/{}:{}", docker_config.address, docker_config.namespace, docker_config.image, docker_config.version);
+        let (dockerfile_file_name, dockerfile_file_path_str, nginx_file_path_str) = Self::write_build_files(docker_config, &func)?;
+        let image = Self::get_image(docker_config);
 
         // 判断是否有 buildx 命令, 如果没有直接用 build 就行
         let str = CommandHandler::exec_command_result("docker buildx version");
@@ -85,6 +71,8 @@ impl DockerHandler {
             info!("docker `buildx` not found, use docker `build` !")
         }
 
+        let build_flags = Self::get_build_flags(&docker_config, !docker_buildx.is_empty());
+
         if docker_config.need_push == "Yes" {
             let pull_nginx_command_list = Self::exec_docker_pull_nginx(&docker_config);
             if pull_nginx_command_list.is_empty() {
@@ -98,13 +86,23 @@ impl DockerHandler {
             for pull_nginx_command in pull_nginx_command_list.iter() {
                 commands.push(pull_nginx_command.to_string());
             }
-            commands.push(format!("docker {} build --file ./{} -t {} --platform {} -o type=docker .", docker_buildx, dockerfile_file_name, image, docker_config.platform));
+            commands.push(format!(
+                "docker {} build{} --file ./{} -t {} --platform {} -o type=docker .",
+                docker_buildx, build_flags, dockerfile_file_name, image, docker_config.platform
+            ));
             commands.push(format!("docker push {}", image));
+
+            // 额外的 tag
+            for tag in Self::get_extra_tags(docker_config).iter() {
+                let extra_image = format!("{}:{}", Self::get_image_repo(docker_config), tag);
+                commands.push(format!("docker tag {} {}", image, extra_image));
+                commands.push(format!("docker push {}", extra_image));
+            }
         } else {
             // 不需要推送，直接打本地包
             commands.push(format!(
-                "docker {} build --file ./{} -t {}:{} --platform {} -o type=docker .",
-                docker_buildx, dockerfile_file_name, docker_config.image, docker_config.version, docker_config.platform
+                "docker {} build{} --file ./{} -t {}:{} --platform {} -o type=docker .",
+                docker_buildx, build_flags, dockerfile_file_name, docker_config.image, docker_config.version, docker_config.platform
             ));
         }
 
@@ -122,7 +120,9 @@ impl DockerHandler {
             if !success {
                 FileHandler::delete_file(&dockerfile_file_path_str)?; // 删除 Dockerfile 文件
                 FileHandler::delete_file(&nginx_file_path_str)?; // 删除 nginx.conf 文件
-                return Err(Error::convert_string(&format!("run docker command failed: {}", command)));
+                let logs = logs.lock().unwrap();
+                let last_logs = logs[logs.len().saturating_sub(LAST_LOG_LINES)..].join("\n");
+                return Err(Error::convert_string(&format!("run docker command failed: {}, output:\n{}", command, last_logs)));
             }
         }
 
@@ -130,21 +130,226 @@ impl DockerHandler {
         FileHandler::delete_file(&nginx_file_path_str)?; // 删除 nginx.conf 文件
 
         info!("run docker commands success !");
+        let mut success = true;
         if docker_config.need_push == "Yes" {
-            let func_cloned = func_cloned.clone();
-            return Self::update_image(
+            let func_clone = func_cloned.clone();
+            success = Self::update_image(
                 &docker_config,
                 &image,
                 move |msg| {
-                    let func = func_cloned.read().unwrap();
+                    let func = func_clone.read().unwrap();
                     (*func)(msg);
                 },
                 server,
             )
-            .await;
+            .await?;
+
+            // 清理本地镜像
+            if success && docker_config.prune_after_push {
+                let func = func_cloned.read().unwrap();
+                Self::prune_images(docker_config, &image, &*func);
+            }
         }
 
-        return Ok(true);
+        let log = logs.lock().unwrap().clone();
+        return Ok(DockerResult { success, log });
+    }
+
+    /// 删除构建的镜像和悬空镜像, 失败时只输出警告
+    fn prune_images<F>(docker_config: &DockerConfig, image: &str, func: &F)
+    where
+        F: Fn(&str),
+    {
+        let mut images = vec![image.to_string()];
+        for tag in Self::get_extra_tags(docker_config).iter() {
+            images.push(format!("{}:{}", Self::get_image_repo(docker_config), tag));
+        }
+
+        let commands = vec![format!("docker rmi {}", images.join(" ")), String::from("docker image prune -f")];
+        for command in commands.iter() {
+            func(&format!("exec command: {}", command));
+            let (success, lines) = CommandHandler::exec_command(command);
+            for line in lines.iter() {
+                func(line);
+            }
+
+            if !success {
+                let msg = format!("warning: run docker command failed: {}, ignore it !", command);
+                warn!("{}", &msg);
+                func(&msg);
+            }
+        }
+    }
+
+    /// 同 `exec`, 输出按行解析为 `BuildEvent`, 用于按构建步骤展示进度
+    pub async fn exec_with_events<E>(docker_config: &DockerConfig, server: &sftp::config::Server, on_event: E) -> Result<DockerResult, String>
+    where
+        E: Fn(BuildEvent) + Send + Sync + 'static,
+    {
+        return Self::exec(docker_config, server, move |msg: &str| {
+            for line in msg.lines() {
+                on_event(BuildEvent::parse(line));
+            }
+        })
+        .await;
+    }
+
+    /// 创建 nginx.conf 和 Dockerfile 文件, 返回 Dockerfile 文件名、Dockerfile 路径和 nginx.conf 路径
+    fn write_build_files<F>(docker_config: &DockerConfig, func: &F) -> Result<(String, String, String), String>
+    where
+        F: Fn(&str),
+    {
+        Self::check_pairs("build arg", &docker_config.build_args)?;
+        Self::check_pairs("env", &docker_config.env)?;
+
+        let time = Utils::get_date(Some("%Y%m%d%H%M%S".to_string()));
+
+        // 创建 nginx.conf 文件
+        let nginx_file_name = format!("nginx_{}.conf", time); // nginx 文件名
+        let nginx_file_path = Path::new(&docker_config.dir).join(&nginx_file_name);
+        let nginx_file_path_str = nginx_file_path.to_string_lossy().to_string();
+        FileHandler::write_to_file_when_clear(&nginx_file_path_str, &docker_config.nginx_content)?;
+
+        let mut dockerfile_content = docker_config.dockerfile.clone();
+
+        // 添加 nginx
+        if !docker_config.nginx_path.is_empty() && !docker_config.nginx_content.is_empty() {
+            let mut content: Vec<String> = dockerfile_content.lines().map(String::from).collect();
+            content.push(format!("ADD {} {}", nginx_file_name, docker_config.nginx_path));
+
+            if !docker_config.deploy_dir.is_empty() {
+                content.push(format!("COPY {} ./", docker_config.deploy_dir));
+            }
+
+            dockerfile_content = content.join("\n");
+        }
+
+        // 添加 ENV
+        for (key, value) in docker_config.env.iter() {
+            dockerfile_content.push_str(&format!("\nENV {}=\"{}\"", key.trim(), value.replace('\\', "\\\\").replace('"', "\\\"")));
+        }
+
+        if let Some(cmd) = Self::get_cmd(docker_config) {
+            dockerfile_content.push_str(&format!("\n{}", cmd));
+        }
+
+        // 创建 Dockerfile 文件
+        let dockerfile_file_name = format!("Dockerfile_{}", time); // dockerfile 文件名
+        let dockerfile_file_path = Path::new(&docker_config.dir).join(&dockerfile_file_name);
+        let dockerfile_file_path_str = dockerfile_file_path.to_string_lossy().to_string();
+        FileHandler::write_to_file_when_clear(&dockerfile_file_path_str, &dockerfile_content)?;
+        let msg = format!("Dockerfile content: \n{:#?}", dockerfile_content);
+        func(&msg);
+
+        return Ok((dockerfile_file_name, dockerfile_file_path_str, nginx_file_path_str));
+    }
+
+    /// 获取启动命令, 优先使用 `cmd`, Dockerfile 中已有 CMD/ENTRYPOINT 时不添加, 添加了 nginx 时默认启动 nginx
+    fn get_cmd(docker_config: &DockerConfig) -> Option<String> {
+        if let Some(cmd) = &docker_config.cmd {
+            let cmd = cmd.trim();
+            if !cmd.is_empty() {
+                let upper = cmd.to_uppercase();
+                if upper.starts_with("CMD ") || upper.starts_with("ENTRYPOINT ") {
+                    return Some(cmd.to_string());
+                }
+
+                return Some(format!("CMD {}", cmd));
+            }
+        }
+
+        let has_cmd = docker_config.dockerfile.lines().any(|line| {
+            let line = line.trim_start().to_uppercase();
+            line.starts_with("CMD ") || line.starts_with("ENTRYPOINT ")
+        });
+
+        if has_cmd || docker_config.nginx_path.is_empty() || docker_config.nginx_content.is_empty() {
+            return None;
+        }
+
+        return Some("CMD [\"nginx\", \"-g\", \"daemon off;\"]".to_string());
+    }
+
+    /// 获取远程仓库镜像地址
+    fn get_image(docker_config: &DockerConfig) -> String {
+        return format!("{}:{}", Self::get_image_repo(docker_config), docker_config.version);
+    }
+
+    /// 获取远程仓库镜像名, 不包含 tag
+    fn get_image_repo(docker_config: &DockerConfig) -> String {
+        return format!("{}/{}/{}", docker_config.address, docker_config.namespace, docker_config.image);
+    }
+
+    /// 获取额外的 tag, 忽略空 tag、重复 tag 和与 version 相同的 tag
+    fn get_extra_tags(docker_config: &DockerConfig) -> Vec<String> {
+        let mut tags: Vec<String> = Vec::new();
+        for tag in docker_config.extra_tags.iter() {
+            let tag = tag.trim();
+            if tag.is_empty() || tag == docker_config.version || tags.iter().any(|t| t == tag) {
+                continue;
+            }
+
+            tags.push(tag.to_string());
+        }
+
+        return tags;
+    }
+
+    /// 获取 build 参数, 如 `--no-cache`、`--pull`、`--builder`
+    fn get_build_flags(docker_config: &DockerConfig, has_buildx: bool) -> String {
+        let mut flags = String::new();
+        if docker_config.no_cache {
+            flags.push_str(" --no-cache");
+        }
+
+        if docker_config.pull {
+            flags.push_str(" --pull");
+        }
+
+        for (key, value) in docker_config.build_args.iter() {
+            flags.push_str(&format!(" --build-arg {}", Self::quote_arg(&format!("{}={}", key.trim(), value))));
+        }
+
+        if let Some(builder) = &docker_config.builder {
+            if !builder.is_empty() {
+                if has_buildx {
+                    flags.push_str(&format!(" --builder {}", builder));
+                } else {
+                    info!("docker `buildx` not found, ignore builder: {}", builder);
+                }
+            }
+        }
+
+        return flags;
+    }
+
+    /// 检查 key 不能为空, 且不能包含空格和 `=`
+    fn check_pairs(name: &str, pairs: &[(String, String)]) -> Result<(), String> {
+        for (key, _) in pairs.iter() {
+            let key = key.trim();
+            if key.is_empty() || key.contains(char::is_whitespace) || key.contains('=') {
+                return Err(Error::convert_string(&format!("invalid docker {} key: `{}` !", name, key)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 包含空格或特殊字符的参数使用双引号包裹
+    fn quote_arg(arg: &str) -> String {
+        if !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "_-.=/:,@+".contains(c)) {
+            return arg.to_string();
+        }
+
+        let mut quoted = String::from("\"");
+        for c in arg.chars() {
+            if matches!(c, '"' | '\\' | '$' | '`') {
+                quoted.push('\\');
+            }
+            quoted.push(c);
+        }
+        quoted.push('"');
+        return quoted;
     }
 
     //  拉取 nginx 镜像 docker pull xxx
@@ -188,6 +393,10 @@ impl DockerHandler {
     where
         F: Fn(&str) + Send + Sync + 'static,
     {
+        if docker_config.kubernetes_strategy == KubernetesStrategy::Api {
+            return Self::update_image_by_kubernetes_api(docker_config, image, func).await;
+        }
+
         let func_cloned = Arc::new(Mutex::new(func));
 
         {
@@ -344,6 +553,16 @@ impl DockerHandler {
             return Ok(false);
         }
 
+        // 等待 rollout 完成
+        let rollout_timeout = docker_config.rollout_timeout_secs.unwrap_or(DEFAULT_ROLLOUT_TIMEOUT);
+        if rollout_timeout > 0 {
+            let func_clone = func_cloned.clone();
+            Self::wait_rollout_status(&session, docker_config, &login_cmd, rollout_timeout, move |msg| {
+                let func = func_clone.lock().unwrap();
+                (*func)(&msg);
+            })?;
+        }
+
         // 执行 shell 脚本
         let shell = docker_config.shell.clone();
         if let Some(shell) = shell {
@@ -391,6 +610,52 @@ impl DockerHandler {
         Ok(true)
     }
 
+    /// 等待 `kubectl rollout status` 完成, 超时或失败时返回 pod 状态
+    fn wait_rollout_status<F>(session: &Session, docker_config: &DockerConfig, login_cmd: &str, timeout: u64, func: F) -> Result<(), String>
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        let cmd = format!("{} kubectl rollout status deployment/{} -n {} --timeout={}s", login_cmd, docker_config.image, docker_config.kubernetes_namespace, timeout);
+        let (output, error, exit_status) = Self::exec_remote_command_with_status(session, &cmd, "exec command `kubectl rollout status` error", &func)?;
+        func(&format!("rollout status output info: {}{}", output, error));
+
+        if exit_status == 0 && output.contains("successfully rolled out") {
+            func("rollout success ...");
+            return Ok(());
+        }
+
+        // 获取 pod 状态
+        let cmd = format!("{} kubectl get pod -n {} | grep {}", login_cmd, docker_config.kubernetes_namespace, docker_config.image);
+        let (pod_status, _, _) = Self::exec_remote_command_with_status(session, &cmd, "kubectl get pod status error", &func)?;
+        return Err(Error::convert_string(&format!(
+            "deployment `{}` rollout failed in {}s, exit code: {}, output: {}{}\npod status:\n{}",
+            docker_config.image,
+            timeout,
+            exit_status,
+            output.trim(),
+            error.trim(),
+            pod_status.trim()
+        )));
+    }
+
+    /// 通过 Kubernetes API 更新镜像
+    async fn update_image_by_kubernetes_api<F>(docker_config: &DockerConfig, image: &str, func: F) -> Result<bool, String>
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        #[cfg(feature = "kube")]
+        {
+            let timeout = docker_config.rollout_timeout_secs.unwrap_or(DEFAULT_ROLLOUT_TIMEOUT);
+            return Self::update_image_by_api(docker_config, image, timeout, func).await;
+        }
+
+        #[cfg(not(feature = "kube"))]
+        {
+            let _ = (docker_config, image, func);
+            return Err(Error::convert_string("`Api` kubernetes strategy requires `kube` feature !"));
+        }
+    }
+
     fn get_pod_name<F>(session: &Session, docker_config: &DockerConfig, login_cmd: &str, func: F) -> Result<String, String>
     where
         F: Fn(&str) + Send + Sync + 'static,
@@ -482,6 +747,28 @@ impl DockerHandler {
         return Ok(false);
     }
 
+    /// 执行远程命令, 返回标准输出、错误输出和退出码
+    fn exec_remote_command_with_status<F>(session: &Session, cmd: &str, error_msg: &str, func: &F) -> Result<(String, String, i32), String>
+    where
+        F: Fn(&str),
+    {
+        let msg = format!("exec remote command: {}", cmd);
+        func(&msg);
+
+        let mut channel = SftpHandler::create_channel(&session)?;
+        channel.exec(&cmd).map_err(|err| {
+            let msg = format!("{}: {:#?}", error_msg, err);
+            error!("{}", &msg);
+            SftpHandler::close_channel_in_err(&mut channel);
+            Error::convert_string(&msg)
+        })?;
+
+        let (output, error) = SftpHandler::get_channel_output(&mut channel)?;
+        SftpHandler::close_channel(&mut channel)?;
+        let exit_status = SftpHandler::get_channel_exit_status(&mut channel)?;
+        Ok((output, error, exit_status))
+    }
+
     /// 执行远程命令
     fn exec_remote_command<F>(session: &Session, cmd: &str, error_msg: &str, func: F) -> Result<String, String>
     where
@@ -0,0 +1,156 @@
+//! 使用 `bollard` 构建、推送镜像, 不依赖 docker 命令行, 需要开启 `bollard` feature
+
+use super::DockerHandler;
+use crate::error::Error;
+use crate::{DockerConfig, DockerResult};
+use bollard::auth::DockerCredentials;
+use bollard::image::{BuildImageOptions, PushImageOptions, TagImageOptions};
+use bollard::Docker;
+use futures_util::StreamExt;
+use handlers::file::FileHandler;
+use log::info;
+use std::sync::{Arc, Mutex, RwLock};
+
+impl DockerHandler {
+    /// 使用 `bollard` 构建镜像, 需要推送时打 tag 并推送到远程仓库, 再更新 `kubectl` 中的镜像
+    /// 构建输出通过 func 输出, 构建失败时返回 docker 返回的错误信息
+    pub async fn exec_with_bollard<F>(docker_config: &DockerConfig, server: &sftp::config::Server, func: F) -> Result<DockerResult, String>
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        // 收集所有输出日志
+        let logs: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+        let logs_cloned = logs.clone();
+        let func = move |msg: &str| {
+            logs_cloned.lock().unwrap().push(msg.to_string());
+            func(msg);
+        };
+
+        let msg = format!("docker config: {:#?}", docker_config);
+        func(&msg);
+
+        if DockerConfig::is_empty(&docker_config) {
+            return Err(Error::convert_string("run pipeline failed, `docker config some field` is empty!"));
+        }
+
+        // 连接 docker
+        let docker = Docker::connect_with_local_defaults().map_err(|err| Error::convert_string(&format!("connect to docker error: {:?}", err)))?;
+        docker.ping().await.map_err(|err| Error::convert_string(&format!("`docker` is not running: {:?}", err)))?;
+
+        let (dockerfile_file_name, dockerfile_file_path_str, nginx_file_path_str) = Self::write_build_files(docker_config, &func)?;
+        let result = Self::build_and_push_with_bollard(&docker, docker_config, &dockerfile_file_name, &func).await;
+        FileHandler::delete_file(&dockerfile_file_path_str)?; // 删除 Dockerfile 文件
+        FileHandler::delete_file(&nginx_file_path_str)?; // 删除 nginx.conf 文件
+        let image = result?;
+
+        info!("build docker image by bollard success !");
+        let mut success = true;
+        if docker_config.need_push == "Yes" {
+            let func_cloned = Arc::new(RwLock::new(func));
+            success = Self::update_image(
+                &docker_config,
+                &image,
+                move |msg| {
+                    let func = func_cloned.read().unwrap();
+                    (*func)(msg);
+                },
+                server,
+            )
+            .await?;
+        }
+
+        let log = logs.lock().unwrap().clone();
+        return Ok(DockerResult { success, log });
+    }
+
+    /// 构建镜像, 需要推送时打 tag 并推送, 返回镜像地址
+    async fn build_and_push_with_bollard<F>(docker: &Docker, docker_config: &DockerConfig, dockerfile_file_name: &str, func: &F) -> Result<String, String>
+    where
+        F: Fn(&str),
+    {
+        let local_image = format!("{}:{}", docker_config.image, docker_config.version);
+        let context = Self::get_build_context(&docker_config.dir)?;
+        let options = BuildImageOptions {
+            dockerfile: dockerfile_file_name.to_string(),
+            t: local_image.clone(),
+            platform: docker_config.platform.clone(),
+            nocache: docker_config.no_cache,
+            pull: docker_config.pull,
+            rm: true,
+            buildargs: docker_config.build_args.iter().map(|(key, value)| (key.trim().to_string(), value.to_string())).collect(),
+            ..Default::default()
+        };
+
+        func(&format!("build image {} ...", local_image));
+        let mut stream = docker.build_image(options, None, Some(context.into()));
+        while let Some(info) = stream.next().await {
+            let info = info.map_err(|err| Error::convert_string(&format!("build image {} error: {:?}", local_image, err)))?;
+            if let Some(error) = info.error {
+                return Err(Error::convert_string(&format!("build image {} error: {}", local_image, error)));
+            }
+
+            if let Some(output) = info.stream {
+                let output = output.trim_end();
+                if !output.is_empty() {
+                    func(output);
+                }
+            }
+        }
+
+        // 不需要推送，直接打本地包
+        if docker_config.need_push != "Yes" {
+            return Ok(local_image);
+        }
+
+        let repo = Self::get_image_repo(docker_config);
+        let mut tags = vec![docker_config.version.clone()];
+        tags.extend(Self::get_extra_tags(docker_config));
+
+        for tag in tags.iter() {
+            Self::push_with_bollard(docker, docker_config, &local_image, &repo, tag, func).await?;
+        }
+
+        return Ok(Self::get_image(docker_config));
+    }
+
+    /// 打 tag 并推送到远程仓库
+    async fn push_with_bollard<F>(docker: &Docker, docker_config: &DockerConfig, local_image: &str, repo: &str, tag: &str, func: &F) -> Result<(), String>
+    where
+        F: Fn(&str),
+    {
+        let image = format!("{}:{}", repo, tag);
+        docker
+            .tag_image(local_image, Some(TagImageOptions { repo: repo.to_string(), tag: tag.to_string() }))
+            .await
+            .map_err(|err| Error::convert_string(&format!("tag image {} to {} error: {:?}", local_image, image, err)))?;
+
+        let credentials = DockerCredentials {
+            username: Some(docker_config.user.clone()),
+            password: Some(docker_config.password.clone()),
+            serveraddress: Some(docker_config.address.clone()),
+            ..Default::default()
+        };
+
+        func(&format!("push image {} ...", image));
+        let mut stream = docker.push_image(repo, Some(PushImageOptions { tag: tag.to_string() }), Some(credentials));
+        while let Some(info) = stream.next().await {
+            let info = info.map_err(|err| Error::convert_string(&format!("push image {} error: {:?}", image, err)))?;
+            if let Some(error) = info.error {
+                return Err(Error::convert_string(&format!("push image {} error: {}", image, error)));
+            }
+
+            if let Some(status) = info.status {
+                func(&format!("{} {}", status, info.progress.unwrap_or_default()).trim_end());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 将目录打包成 tar 作为构建上下文
+    fn get_build_context(dir: &str) -> Result<Vec<u8>, String> {
+        let mut builder = tar::Builder::new(Vec::new());
+        builder.append_dir_all(".", dir).map_err(|err| Error::convert_string(&format!("create build context from {} error: {:?}", dir, err)))?;
+        return builder.into_inner().map_err(|err| Error::convert_string(&format!("create build context from {} error: {:?}", dir, err)));
+    }
+}
@@ -0,0 +1,123 @@
+//! 使用 `kube` 通过 kubeconfig 直接调用 Kubernetes API 更新镜像, 不需要 ssh 和远程 kubectl, 需要开启 `kube` feature
+
+use super::DockerHandler;
+use crate::error::Error;
+use crate::DockerConfig;
+use k8s_openapi::api::apps::v1::Deployment;
+use kube::api::{Api, Patch, PatchParams};
+use kube::config::{KubeConfigOptions, Kubeconfig};
+use kube::{Client, Config};
+use std::time::{Duration, Instant};
+
+const ROLLOUT_POLL_INTERVAL: Duration = Duration::from_secs(2); // 检查 rollout 状态的间隔
+
+impl DockerHandler {
+    /// 通过 Kubernetes API 更新 deployment 的镜像并重启, 然后等待 rollout 完成
+    pub(crate) async fn update_image_by_api<F>(docker_config: &DockerConfig, image: &str, timeout: u64, func: F) -> Result<bool, String>
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        func("update `image` by kubernetes api ...");
+
+        let client = Self::get_kube_client(docker_config).await?;
+        let deployments: Api<Deployment> = Api::namespaced(client, &docker_config.kubernetes_namespace);
+
+        // 按容器名合并, 不影响其他容器
+        let patch = serde_json::json!({
+            "spec": {
+                "template": {
+                    "spec": {
+                        "containers": [
+                            {
+                                "name": docker_config.image,
+                                "image": image,
+                                "imagePullPolicy": "Always"
+                            }
+                        ]
+                    }
+                }
+            }
+        });
+
+        deployments
+            .patch(&docker_config.image, &PatchParams::default(), &Patch::Strategic(&patch))
+            .await
+            .map_err(|err| Error::convert_string(&format!("patch deployment `{}` error: {:?}", docker_config.image, err)))?;
+        func("update `image` by kubernetes api success ...");
+
+        // 重启, 镜像 tag 未变化时也会拉取新镜像
+        deployments
+            .restart(&docker_config.image)
+            .await
+            .map_err(|err| Error::convert_string(&format!("restart deployment `{}` error: {:?}", docker_config.image, err)))?;
+        func(&format!("restart deployment `{}` success ...", docker_config.image));
+
+        if timeout > 0 {
+            Self::wait_rollout_by_api(&deployments, &docker_config.image, timeout, &func).await?;
+        }
+
+        if docker_config.shell.as_ref().map(|shell| !shell.is_empty()).unwrap_or(false) {
+            func("`shell` is only supported by `Ssh` kubernetes strategy, ignore it !");
+        }
+
+        Ok(true)
+    }
+
+    /// 获取 Kubernetes client, 优先使用 `kubeconfig`, 否则使用 `KUBECONFIG` 或 `~/.kube/config`
+    async fn get_kube_client(docker_config: &DockerConfig) -> Result<Client, String> {
+        let config = match docker_config.kubeconfig.as_deref().map(|path| path.trim()).filter(|path| !path.is_empty()) {
+            Some(path) => {
+                let kubeconfig = Kubeconfig::read_from(path).map_err(|err| Error::convert_string(&format!("read kubeconfig `{}` error: {:?}", path, err)))?;
+                Config::from_custom_kubeconfig(kubeconfig, &KubeConfigOptions::default())
+                    .await
+                    .map_err(|err| Error::convert_string(&format!("load kubeconfig `{}` error: {:?}", path, err)))?
+            }
+            None => Config::infer().await.map_err(|err| Error::convert_string(&format!("infer kubeconfig error: {:?}", err)))?,
+        };
+
+        return Client::try_from(config).map_err(|err| Error::convert_string(&format!("create kubernetes client error: {:?}", err)));
+    }
+
+    /// 等待所有副本更新并可用
+    async fn wait_rollout_by_api<F>(deployments: &Api<Deployment>, name: &str, timeout: u64, func: &F) -> Result<(), String>
+    where
+        F: Fn(&str),
+    {
+        let start_time = Instant::now();
+        loop {
+            let deployment = deployments.get(name).await.map_err(|err| Error::convert_string(&format!("get deployment `{}` error: {:?}", name, err)))?;
+            let generation = deployment.metadata.generation.unwrap_or(0);
+            let replicas = deployment.spec.as_ref().and_then(|spec| spec.replicas).unwrap_or(1);
+            let status = deployment.status.unwrap_or_default();
+            let updated = status.updated_replicas.unwrap_or(0);
+            let available = status.available_replicas.unwrap_or(0);
+            let total = status.replicas.unwrap_or(0);
+
+            if status.observed_generation.unwrap_or(0) >= generation && updated >= replicas && available >= replicas && total == updated {
+                func(&format!("deployment `{}` successfully rolled out ...", name));
+                return Ok(());
+            }
+
+            if start_time.elapsed() >= Duration::from_secs(timeout) {
+                let conditions: Vec<String> = status
+                    .conditions
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|condition| format!("{}={} {}", condition.type_, condition.status, condition.message.clone().unwrap_or_default()))
+                    .collect();
+                return Err(Error::convert_string(&format!(
+                    "deployment `{}` rollout failed in {}s, replicas: {}, updated: {}, available: {}, conditions: {}",
+                    name,
+                    timeout,
+                    replicas,
+                    updated,
+                    available,
+                    conditions.join("; ")
+                )));
+            }
+
+            func(&format!("waiting for deployment `{}` rollout, updated: {}/{}, available: {}/{}", name, updated, replicas, available, replicas));
+            tokio::time::sleep(ROLLOUT_POLL_INTERVAL).await;
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! 构建输出事件, 将 docker build 的输出解析为结构化事件, 支持 `docker build` 和 `docker buildx build` 的输出
+
+/// 构建事件
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildEvent {
+    Step { n: u32, total: u32, text: String }, // 构建步骤, 如 `Step 2/5 : RUN npm install`、`#6 [2/5] RUN npm install`
+    Pull { image: String },                    // 拉取镜像
+    Error { msg: String },                     // 错误信息
+    Raw(String),                               // 其他输出
+}
+
+impl BuildEvent {
+    /// 解析一行输出
+    pub fn parse(line: &str) -> BuildEvent {
+        let line = line.trim();
+
+        // buildx 输出以 `#<编号> ` 开头
+        let content = match line.strip_prefix('#') {
+            Some(rest) => match rest.split_once(' ') {
+                Some((id, content)) if !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()) => content.trim(),
+                _ => line,
+            },
+            None => line,
+        };
+
+        // 错误
+        if content.starts_with("ERROR") || content.starts_with("error:") || content.starts_with("Error response from daemon") {
+            return BuildEvent::Error { msg: content.to_string() };
+        }
+
+        // docker build: `Step 2/5 : RUN npm install`
+        if let Some(rest) = content.strip_prefix("Step ") {
+            if let Some((step, text)) = rest.split_once(" : ") {
+                if let Some((n, total)) = Self::parse_step(step) {
+                    return BuildEvent::Step { n, total, text: text.trim().to_string() };
+                }
+            }
+        }
+
+        // buildx: `[2/5] RUN npm install`、`[builder 2/5] RUN npm install`
+        if let Some(rest) = content.strip_prefix('[') {
+            if let Some((stage, text)) = rest.split_once(']') {
+                if let Some((n, total)) = stage.split_whitespace().last().and_then(Self::parse_step) {
+                    return BuildEvent::Step { n, total, text: text.trim().to_string() };
+                }
+            }
+        }
+
+        // 拉取镜像: `latest: Pulling from library/nginx`、`[internal] load metadata for docker.io/library/nginx:latest`
+        if let Some((_, image)) = content.split_once("Pulling from ") {
+            return BuildEvent::Pull { image: image.trim().to_string() };
+        }
+
+        if let Some((_, image)) = content.split_once("load metadata for ") {
+            return BuildEvent::Pull { image: image.trim().to_string() };
+        }
+
+        return BuildEvent::Raw(line.to_string());
+    }
+
+    /// 解析 `2/5`
+    fn parse_step(step: &str) -> Option<(u32, u32)> {
+        let (n, total) = step.trim().split_once('/')?;
+        return Some((n.parse().ok()?, total.parse().ok()?));
+    }
+}
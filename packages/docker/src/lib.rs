@@ -1,23 +1,50 @@
 pub mod docker;
 pub mod error;
+pub mod event;
 
 #[derive(Default, Debug, Clone)]
 pub struct DockerConfig {
-    pub dir: String,                  // 执行命令的目录
-    pub dockerfile: String,           // Dockerfile 文件
-    pub address: String,              // 远程仓库 address
-    pub image: String,                // docker image
-    pub version: String,              // docker image version
-    pub namespace: String,            // 远程仓库 namespace
-    pub user: String,                 // 远程仓库 用户名
-    pub password: String,             // 远程仓库 密码
-    pub need_push: String,            // 是否推送到远程仓库
-    pub nginx_path: String,           // docker 中 nginx 地址
-    pub nginx_content: String,        // docker 中 nginx 内容(如果需要拷贝 nginx)
-    pub platform: String,             // docker 打包平台
-    pub deploy_dir: String,           // 发布目录
-    pub kubernetes_namespace: String, // Kubernetes 命名空间名称
-    pub shell: Option<String>,        // Pod 中需要执行的脚本
+    pub dir: String,                             // 执行命令的目录
+    pub dockerfile: String,                      // Dockerfile 文件
+    pub address: String,                         // 远程仓库 address
+    pub image: String,                           // docker image
+    pub version: String,                         // docker image version
+    pub namespace: String,                       // 远程仓库 namespace
+    pub user: String,                            // 远程仓库 用户名
+    pub password: String,                        // 远程仓库 密码
+    pub need_push: String,                       // 是否推送到远程仓库
+    pub nginx_path: String,                      // docker 中 nginx 地址
+    pub nginx_content: String,                   // docker 中 nginx 内容(如果需要拷贝 nginx)
+    pub platform: String,                        // docker 打包平台
+    pub deploy_dir: String,                      // 发布目录
+    pub kubernetes_namespace: String,            // Kubernetes 命名空间名称
+    pub shell: Option<String>,                   // Pod 中需要执行的脚本
+    pub no_cache: bool,                          // 构建时不使用缓存 `--no-cache`
+    pub pull: bool,                              // 构建时总是拉取最新的基础镜像 `--pull`
+    pub builder: Option<String>,                 // 指定 buildx builder `--builder`, 只在 buildx 下生效
+    pub build_args: Vec<(String, String)>,       // 构建参数 `--build-arg KEY=VALUE`
+    pub env: Vec<(String, String)>,              // 在 Dockerfile CMD 之前注入的 `ENV KEY="VALUE"`
+    pub cmd: Option<String>,                     // 追加的启动命令, 如 `CMD ["node", "server.js"]` 或 `ENTRYPOINT [...]`, 不带指令时作为 CMD
+    pub extra_tags: Vec<String>,                 // 额外推送的 tag, 如 `latest`、git sha, 与 version 指向同一个镜像
+    pub rollout_timeout_secs: Option<u64>,       // 重启后等待 `kubectl rollout status` 完成的超时时间(秒), 默认为 300, 为 0 时不等待
+    pub prune_after_push: bool,                  // 推送和更新成功后删除本地镜像并执行 `docker image prune -f`, 失败时保留镜像用于排查
+    pub kubernetes_strategy: KubernetesStrategy, // 更新 Kubernetes 镜像的方式, 默认通过 ssh 执行 kubectl
+    pub kubeconfig: Option<String>,              // `Api` 方式使用的 kubeconfig 路径, 为空时使用 `KUBECONFIG` 或 `~/.kube/config`
+}
+
+/// 更新 Kubernetes 镜像的方式
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum KubernetesStrategy {
+    #[default]
+    Ssh, // 通过 ssh 登录服务器执行 kubectl, 适用于内网隔离环境
+    Api, // 通过 kubeconfig 直接调用 Kubernetes API, 需要开启 `kube` feature
+}
+
+/// docker 执行结果
+#[derive(Default, Debug, Clone)]
+pub struct DockerResult {
+    pub success: bool,    // 是否成功
+    pub log: Vec<String>, // 完整的命令输出日志
 }
 
 impl DockerConfig {
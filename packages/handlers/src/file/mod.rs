@@ -67,12 +67,36 @@ impl FileHandler {
 
     /// 清空文件并写入新的内容
     pub fn write_to_file_when_clear(file_path: &str, content: &str) -> Result<(), String> {
+        Self::write_to_file_with_mode(file_path, content, None)
+    }
+
+    /// 清空文件并写入新的内容, 写入后设置文件权限, mode 为空时使用系统默认(umask)
+    pub fn write_to_file_with_mode(file_path: &str, content: &str, mode: Option<u32>) -> Result<(), String> {
         // 打开文件以进行覆盖写入
         let mut file = File::create(&file_path).map_err(|err| Error::Error(err.to_string()).to_string())?;
         file.write_all(content.as_bytes()).map_err(|err| Error::Error(err.to_string()).to_string())?;
         file.flush().unwrap(); // 刷新文件缓冲
         file.sync_all().unwrap(); // 写入磁盘
         drop(file); // 自动关闭文件
+
+        if let Some(mode) = mode {
+            Self::set_file_mode(file_path, mode)?;
+        }
+
+        Ok(())
+    }
+
+    /// 设置文件权限, 非 unix 系统忽略
+    pub fn set_file_mode(file_path: &str, mode: u32) -> Result<(), String> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(file_path, fs::Permissions::from_mode(mode)).map_err(|err| Error::Error(err.to_string()).to_string())?;
+        }
+
+        #[cfg(not(unix))]
+        let _ = (file_path, mode);
+
         Ok(())
     }
 }
@@ -137,6 +161,24 @@ impl FileHandler {
         Ok((directories, files))
     }
 
+    /// 递归读取目录下的所有文件, 返回文件全路径
+    pub fn read_dir_files<P: AsRef<Path>>(path: P) -> Result<Vec<String>, String> {
+        let entries = fs::read_dir(&path).map_err(|err| Error::Error(format!("read dir `{}` error: {}", path.as_ref().to_string_lossy(), err)).to_string())?;
+        let mut files: Vec<String> = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|err| Error::Error(err.to_string()).to_string())?;
+            let file_path = entry.path();
+
+            if file_path.is_dir() {
+                files.extend(Self::read_dir_files(&file_path)?);
+            } else {
+                files.push(file_path.to_string_lossy().to_string())
+            }
+        }
+
+        Ok(files)
+    }
+
     /// 获取文件 hash 值
     pub fn get_file_hash(file_path: &str) -> Result<String, String> {
         let path = Path::new(file_path);
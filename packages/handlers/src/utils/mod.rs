@@ -2,13 +2,18 @@
 
 use crate::error::Error;
 use crate::file::FileHandler;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use log::info;
+use rayon::prelude::*;
 use std::fs;
 use std::fs::File;
-use std::io::Write;
-use std::path::Path;
+use std::io;
+use std::io::{Cursor, Write};
+use std::path::{Component, Path, PathBuf};
 use zip::write::{FileOptions, SimpleFileOptions};
-use zip::{CompressionMethod, ZipWriter};
+use zip::{CompressionMethod, DateTime, ZipArchive, ZipWriter};
 
 pub struct Utils;
 
@@ -37,8 +42,47 @@ impl Utils {
         };
     }
 
-    /// 生成 zip 压缩包
+    /// 生成 zip 压缩包, 文件按名称排序且使用固定时间, 相同内容生成的压缩包一致
     pub fn generate_zip(dir: &str, output_file: &str) -> Result<bool, String> {
+        Self::generate_zip_with_filter(dir, output_file, |_| true)
+    }
+
+    /// 生成 zip 压缩包, filter 返回 false 的文件或目录不会被压缩
+    pub fn generate_zip_with_filter<P>(dir: &str, output_file: &str, filter: P) -> Result<bool, String>
+    where
+        P: Fn(&Path) -> bool,
+    {
+        if !output_file.ends_with(".zip") {
+            return Err(Error::convert_string("generate zip failed, `ouput_dir` is not a zip file !"));
+        }
+
+        let path = Path::new(output_file);
+        if path.exists() {
+            FileHandler::delete_file(output_file)?;
+        }
+
+        // 获取相对路径
+        let source_dir_path = Path::new(dir);
+        let relative_path = source_dir_path.strip_prefix(path.parent().unwrap()).unwrap_or(path).to_path_buf();
+        let file = File::create(&path).map_err(|err| Error::Error(err.to_string()).to_string())?;
+        let mut zip = ZipWriter::new(file);
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored).unix_permissions(0o777).last_modified_time(DateTime::default());
+        Self::add_directory_to_zip(&mut zip, &source_dir_path, &relative_path, &options, &filter)?;
+        zip.finish().map_err(|err| Error::Error(err.to_string()).to_string())?;
+        Ok(true)
+    }
+
+    /// 并行生成 zip 压缩包
+    /// 每个文件在线程池中单独压缩(Deflated), 再按顺序写入 zip, 适用于文件较多的大目录
+    pub fn generate_zip_parallel(dir: &str, output_file: &str) -> Result<bool, String> {
+        Self::generate_zip_parallel_with_filter(dir, output_file, |_| true)
+    }
+
+    /// 并行生成 zip 压缩包, filter 返回 false 的文件或目录不会被压缩
+    pub fn generate_zip_parallel_with_filter<P>(dir: &str, output_file: &str, filter: P) -> Result<bool, String>
+    where
+        P: Fn(&Path) -> bool,
+    {
         if !output_file.ends_with(".zip") {
             return Err(Error::convert_string("generate zip failed, `ouput_dir` is not a zip file !"));
         }
@@ -51,20 +95,124 @@ impl Utils {
         // 获取相对路径
         let source_dir_path = Path::new(dir);
         let relative_path = source_dir_path.strip_prefix(path.parent().unwrap()).unwrap_or(path).to_path_buf();
+        let mut entries: Vec<(PathBuf, PathBuf)> = Vec::new();
+        Self::get_zip_entries(&source_dir_path, &relative_path, &mut entries, &filter)?;
+
+        // 并行压缩每个文件
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated).unix_permissions(0o777).last_modified_time(DateTime::default());
+        let buffers: Vec<Result<Vec<u8>, String>> = entries.par_iter().map(|(file_path, file_name)| Self::compress_zip_entry(file_path, file_name, &options)).collect();
+
+        // 按顺序写入 zip
         let file = File::create(&path).map_err(|err| Error::Error(err.to_string()).to_string())?;
         let mut zip = ZipWriter::new(file);
-        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored).unix_permissions(0o777);
-        Self::add_directory_to_zip(&mut zip, &source_dir_path, &relative_path, &options)?;
+        for buffer in buffers {
+            let mut archive = ZipArchive::new(Cursor::new(buffer?)).map_err(|err| Error::Error(err.to_string()).to_string())?;
+            let entry = archive.by_index_raw(0).map_err(|err| Error::Error(err.to_string()).to_string())?;
+            zip.raw_copy_file(entry).map_err(|err| Error::Error(err.to_string()).to_string())?;
+        }
+
         zip.finish().map_err(|err| Error::Error(err.to_string()).to_string())?;
         Ok(true)
     }
 
+    /// 生成 tar.gz 压缩包, 相比 zip 会压缩文件内容, 适用于慢速网络
+    pub fn generate_tar_gz(dir: &str, output_file: &str) -> Result<bool, String> {
+        Self::generate_tar_gz_with_filter(dir, output_file, |_| true)
+    }
+
+    /// 生成 tar.gz 压缩包, 文件按名称排序且使用固定时间, filter 返回 false 的文件或目录不会被压缩
+    pub fn generate_tar_gz_with_filter<P>(dir: &str, output_file: &str, filter: P) -> Result<bool, String>
+    where
+        P: Fn(&Path) -> bool,
+    {
+        if !output_file.ends_with(".tar.gz") {
+            return Err(Error::convert_string("generate tar.gz failed, `ouput_dir` is not a tar.gz file !"));
+        }
+
+        let path = Path::new(output_file);
+        if path.exists() {
+            FileHandler::delete_file(output_file)?;
+        }
+
+        // 获取相对路径
+        let source_dir_path = Path::new(dir);
+        let relative_path = source_dir_path.strip_prefix(path.parent().unwrap()).unwrap_or(path).to_path_buf();
+        let mut entries: Vec<(PathBuf, PathBuf)> = Vec::new();
+        Self::get_zip_entries(&source_dir_path, &relative_path, &mut entries, &filter)?;
+
+        let file = File::create(&path).map_err(|err| Error::Error(err.to_string()).to_string())?;
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        for (file_path, file_name) in entries.iter() {
+            let file_content = fs::read(file_path).map_err(|err| Error::Error(err.to_string()).to_string())?;
+            let mut header = tar::Header::new_gnu();
+            header.set_size(file_content.len() as u64);
+            header.set_mode(0o777);
+            header.set_mtime(0);
+            header.set_cksum();
+            builder.append_data(&mut header, file_name, file_content.as_slice()).map_err(|err| Error::Error(err.to_string()).to_string())?;
+        }
+
+        let encoder = builder.into_inner().map_err(|err| Error::Error(err.to_string()).to_string())?;
+        encoder.finish().map_err(|err| Error::Error(err.to_string()).to_string())?;
+        Ok(true)
+    }
+
+    /// 按文件名排序读取目录, 保证不同机器生成的 zip 包一致
+    fn read_sorted_dir(dir: &Path) -> Result<Vec<fs::DirEntry>, String> {
+        let mut entries = fs::read_dir(dir)
+            .map_err(|err| Error::Error(err.to_string()).to_string())?
+            .collect::<Result<Vec<fs::DirEntry>, io::Error>>()
+            .map_err(|err| Error::Error(err.to_string()).to_string())?;
+        entries.sort_by_key(|entry| entry.file_name());
+        Ok(entries)
+    }
+
+    /// 获取目录下所有文件及其在 zip 包中的名称
+    fn get_zip_entries<P>(source_path: &Path, relative_path: &Path, entries: &mut Vec<(PathBuf, PathBuf)>, filter: &P) -> Result<(), String>
+    where
+        P: Fn(&Path) -> bool,
+    {
+        let dir_entries = Self::read_sorted_dir(source_path)?;
+        for entry in dir_entries {
+            let path = entry.path();
+            if !filter(&path) {
+                continue;
+            }
+
+            let file_name = relative_path.join(entry.file_name());
+
+            if path.is_file() {
+                entries.push((path, file_name));
+            } else if path.is_dir() {
+                Self::get_zip_entries(&path, &file_name, entries, filter)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 压缩单个文件到内存 zip 中
+    fn compress_zip_entry(file_path: &Path, file_name: &Path, options: &FileOptions<()>) -> Result<Vec<u8>, String> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file(file_name.to_str().unwrap(), *options).map_err(|err| Error::Error(err.to_string()).to_string())?;
+        let file_content = fs::read(file_path).map_err(|err| Error::Error(err.to_string()).to_string())?;
+        zip.write_all(&file_content).map_err(|err| Error::Error(err.to_string()).to_string())?;
+        let cursor = zip.finish().map_err(|err| Error::Error(err.to_string()).to_string())?;
+        Ok(cursor.into_inner())
+    }
+
     /// 添加到 zip 包中
-    fn add_directory_to_zip(zip: &mut ZipWriter<File>, source_path: &Path, relative_path: &Path, options: &FileOptions<()>) -> Result<(), String> {
-        let entries = fs::read_dir(source_path).map_err(|err| Error::Error(err.to_string()).to_string())?;
+    fn add_directory_to_zip<P>(zip: &mut ZipWriter<File>, source_path: &Path, relative_path: &Path, options: &FileOptions<()>, filter: &P) -> Result<(), String>
+    where
+        P: Fn(&Path) -> bool,
+    {
+        let entries = Self::read_sorted_dir(source_path)?;
         for entry in entries {
-            let entry = entry.map_err(|err| Error::Error(err.to_string()).to_string())?;
             let path = entry.path();
+            if !filter(&path) {
+                continue;
+            }
+
             let file_name = relative_path.join(entry.file_name());
 
             if path.is_file() {
@@ -74,22 +222,102 @@ impl Utils {
                 zip.write_all(&file_content).map_err(|err| Error::Error(err.to_string()).to_string())?;
             } else if path.is_dir() {
                 // 递归添加子目录及其内容到压缩包
-                Self::add_directory_to_zip(zip, &path, &file_name, options).map_err(|err| Error::Error(err.to_string()).to_string())?;
+                Self::add_directory_to_zip(zip, &path, &file_name, options, filter).map_err(|err| Error::Error(err.to_string()).to_string())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 解压文件到目录, 支持 `.zip`、`.tar.gz` 和 `.tgz`
+    pub fn extract(file_path: &str, output_dir: &str) -> Result<(), String> {
+        let file_name = file_path.to_lowercase();
+        if file_name.ends_with(".zip") {
+            return Self::extract_zip(file_path, output_dir);
+        }
+
+        if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+            return Self::extract_tar_gz(file_path, output_dir);
+        }
+
+        Err(Error::convert_string(&format!("extract file `{}` failed, only support `.zip`、`.tar.gz` and `.tgz` !", file_path)))
+    }
+
+    /// 解压 zip, 拒绝包含 `../` 或绝对路径的文件(zip slip)
+    pub fn extract_zip(file_path: &str, output_dir: &str) -> Result<(), String> {
+        let file = File::open(file_path).map_err(|err| Error::Error(err.to_string()).to_string())?;
+        let mut archive = ZipArchive::new(file).map_err(|err| Error::Error(err.to_string()).to_string())?;
+        let output_dir = Path::new(output_dir);
+        fs::create_dir_all(output_dir).map_err(|err| Error::Error(err.to_string()).to_string())?;
+
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i).map_err(|err| Error::Error(err.to_string()).to_string())?;
+            let entry_path = match entry.enclosed_name() {
+                Some(entry_path) => entry_path,
+                None => return Err(Error::convert_string(&format!("extract zip `{}` failed, unsafe entry path: `{}` !", file_path, entry.name()))),
+            };
+
+            let output_path = output_dir.join(entry_path);
+            if entry.is_dir() {
+                fs::create_dir_all(&output_path).map_err(|err| Error::Error(err.to_string()).to_string())?;
+                continue;
+            }
+
+            if let Some(parent) = output_path.parent() {
+                fs::create_dir_all(parent).map_err(|err| Error::Error(err.to_string()).to_string())?;
+            }
+
+            let mut output_file = File::create(&output_path).map_err(|err| Error::Error(err.to_string()).to_string())?;
+            io::copy(&mut entry, &mut output_file).map_err(|err| Error::Error(err.to_string()).to_string())?;
+        }
+
+        Ok(())
+    }
+
+    /// 解压 tar.gz, 拒绝包含 `../` 或绝对路径的文件(zip slip)
+    pub fn extract_tar_gz(file_path: &str, output_dir: &str) -> Result<(), String> {
+        let file = File::open(file_path).map_err(|err| Error::Error(err.to_string()).to_string())?;
+        let mut archive = tar::Archive::new(GzDecoder::new(file));
+        fs::create_dir_all(output_dir).map_err(|err| Error::Error(err.to_string()).to_string())?;
+
+        let entries = archive.entries().map_err(|err| Error::Error(err.to_string()).to_string())?;
+        for entry in entries {
+            let mut entry = entry.map_err(|err| Error::Error(err.to_string()).to_string())?;
+            let entry_path = entry.path().map_err(|err| Error::Error(err.to_string()).to_string())?.to_path_buf();
+            if entry_path.components().any(|component| !matches!(component, Component::Normal(_) | Component::CurDir)) {
+                return Err(Error::convert_string(&format!("extract tar `{}` failed, unsafe entry path: `{}` !", file_path, entry_path.display())));
             }
+
+            entry.unpack_in(output_dir).map_err(|err| Error::Error(err.to_string()).to_string())?;
         }
 
         Ok(())
     }
 
-    /// 版权所有
-    pub fn copyright() {
-        info!(" ");
-        info!(" ---------------- Copyright ----------------- ");
-        info!("|                                             |");
-        info!("| Author: poohlaha                            |");
-        info!("| Github: https://github.com/poohlaha         |");
-        info!("|                                             |");
-        info!(" ----------------- Copyright ----------------- ");
-        info!(" ");
+    /// 版权所有, 返回版权信息, 由调用方决定是否显示
+    pub fn copyright() -> String {
+        let lines = [
+            " ",
+            " ---------------- Copyright ----------------- ",
+            "|                                             |",
+            "| Author: poohlaha                            |",
+            "| Github: https://github.com/poohlaha         |",
+            "|                                             |",
+            " ----------------- Copyright ----------------- ",
+            " ",
+        ];
+
+        lines.join("\n")
+    }
+
+    /// 输出版权信息到日志, show 为 false 时不输出
+    pub fn log_copyright(show: bool) {
+        if !show {
+            return;
+        }
+
+        for line in Self::copyright().lines() {
+            info!("{}", line);
+        }
     }
 }
@@ -0,0 +1,103 @@
+//! 测试解压
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use handlers::utils::Utils;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+fn create_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("handlers-extract-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn to_str(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+/// 创建 zip, 文件名原样写入, 不做校验
+fn create_zip(file_path: &Path, files: &[(&str, &str)]) {
+    let mut zip = ZipWriter::new(File::create(file_path).unwrap());
+    for (name, content) in files {
+        zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+
+    zip.finish().unwrap();
+}
+
+/// 创建 tar.gz, 文件名直接写入 header, 绕过 `tar` 对 `..` 的校验
+fn create_tar_gz(file_path: &Path, files: &[(&str, &str)]) {
+    let mut builder = tar::Builder::new(GzEncoder::new(File::create(file_path).unwrap(), Compression::default()));
+    for (name, content) in files {
+        let mut header = tar::Header::new_gnu();
+        header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append(&header, content.as_bytes()).unwrap();
+    }
+
+    builder.into_inner().unwrap().finish().unwrap();
+}
+
+#[test]
+fn test_extract_zip() {
+    let dir = create_dir("zip");
+    let file_path = dir.join("archive.zip");
+    create_zip(&file_path, &[("index.html", "<html></html>"), ("static/app.js", "app")]);
+
+    let output_dir = dir.join("output");
+    Utils::extract(&to_str(&file_path), &to_str(&output_dir)).unwrap();
+    assert_eq!(fs::read_to_string(output_dir.join("index.html")).unwrap(), "<html></html>");
+    assert_eq!(fs::read_to_string(output_dir.join("static/app.js")).unwrap(), "app");
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_extract_zip_slip() {
+    let dir = create_dir("zip-slip");
+    let file_path = dir.join("archive.zip");
+    create_zip(&file_path, &[("../evil.txt", "evil")]);
+
+    let output_dir = dir.join("output");
+    assert!(Utils::extract(&to_str(&file_path), &to_str(&output_dir)).is_err());
+    assert!(!dir.join("evil.txt").exists());
+    assert_eq!(fs::read_dir(&output_dir).unwrap().count(), 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_extract_tar_gz() {
+    let dir = create_dir("tar");
+    let file_path = dir.join("archive.tar.gz");
+    create_tar_gz(&file_path, &[("index.html", "<html></html>"), ("static/app.js", "app")]);
+
+    let output_dir = dir.join("output");
+    Utils::extract(&to_str(&file_path), &to_str(&output_dir)).unwrap();
+    assert_eq!(fs::read_to_string(output_dir.join("index.html")).unwrap(), "<html></html>");
+    assert_eq!(fs::read_to_string(output_dir.join("static/app.js")).unwrap(), "app");
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_extract_tar_gz_slip() {
+    let dir = create_dir("tar-slip");
+    let file_path = dir.join("archive.tgz");
+    create_tar_gz(&file_path, &[("../evil.txt", "evil")]);
+
+    let output_dir = dir.join("output");
+    assert!(Utils::extract(&to_str(&file_path), &to_str(&output_dir)).is_err());
+    assert!(!dir.join("evil.txt").exists());
+    assert_eq!(fs::read_dir(&output_dir).unwrap().count(), 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -16,6 +16,11 @@ async fn send_benchmark() {
         headers: None,
         form: None,
         timeout: None,
+        raw_response: None,
+        auth: None,
+        proxy: None,
+        retries: None,
+        retry_on_status: None,
     };
     let response: HttpResponse = HttpClient::send(options, false).await.unwrap();
     assert_eq!(response.status_code, 200);
@@ -1,26 +1,35 @@
 use crate::options::HttpError;
 use crate::LOGGER_PREFIX;
 use colored::*;
+use handlers::file::FileHandler;
+use handlers::utils::Utils;
 use indicatif::{MultiProgress, ProgressBar, ProgressState, ProgressStyle};
-use reqwest::header::CONTENT_LENGTH;
-use reqwest::{Client, Response};
+use reqwest::header::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, RANGE};
+use reqwest::{Client, Response, StatusCode};
 use std::cmp::min;
 use std::ffi::OsStr;
 use std::fmt::Write as ProgressWrite;
 use std::fs;
-use std::fs::File;
-use std::io::Write;
+use std::fs::{File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 pub struct Download;
 
 pub struct DownloadOptions {
-    pub url: String,                // url
-    pub file_name: Option<String>,  // save download url, if null, will use filename by url
-    pub timeout: Option<u64>,       // timeout, default `0`
-    pub output_dir: Option<String>, // output dir
-    pub overwrite: Option<bool>,    // if file exists, will overwrite
+    pub url: String,                     // url
+    pub file_name: Option<String>,       // save download url, if null, will use filename by url
+    pub timeout: Option<u64>,            // timeout, default `0`
+    pub output_dir: Option<String>,      // output dir
+    pub overwrite: Option<bool>,         // if file exists, will overwrite
+    pub cancel: Option<Arc<AtomicBool>>, // set to `true` to cancel the download, checked between chunks
+    pub resume: Option<bool>,            // continue a partial file by `Range` request, default `false`
+    pub expected_sha256: Option<String>, // verify the sha256 checksum after download, the file will be deleted on mismatch
+    pub max_kbps: Option<u64>,           // limit the average download speed(KB/s), default no limit
+    pub connections: Option<u8>,         // parallel `Range` requests when server supports `Accept-Ranges: bytes`, default `1`
 }
 
 #[derive(Default, Debug)]
@@ -29,6 +38,12 @@ pub struct DownloadResult {
     pub success: bool,
     pub file_name: String,
     pub url: String,
+    pub downloaded_size: u64, // downloaded bytes
+    pub duration: Duration,   // download duration
+    pub average_speed: f64,   // average speed, bytes/sec
+    pub cancelled: bool,      // download is cancelled, the partial file has been deleted unless `resume` is enabled
+    pub resumed_size: u64,    // bytes of the partial file before resuming, `0` means download from scratch
+    pub sha256: String,       // sha256 checksum of the downloaded file
 }
 
 impl DownloadResult {
@@ -37,6 +52,20 @@ impl DownloadResult {
     }
 }
 
+/// one connection of the parallel download
+struct DownloadRange {
+    client: Client,
+    url: String,
+    output_file_path: PathBuf,
+    start: u64,
+    end: u64, // inclusive
+    pb: ProgressBar,
+    downloaded_size: Arc<AtomicU64>, // downloaded bytes of all connections
+    cancel: Option<Arc<AtomicBool>>,
+    max_kbps: Option<u64>,
+    start_time: Instant,
+}
+
 const TIMEOUT: u64 = 30;
 impl Download {
     /// get download filename
@@ -60,8 +89,21 @@ impl Download {
         return download_timeout;
     }
 
-    /// get response
-    async fn get_response(options: &DownloadOptions) -> Result<(Response, String), HttpError> {
+    /// get client
+    fn get_client(options: &DownloadOptions) -> Result<Client, HttpError> {
+        let timeout = Download::get_timeout(&options);
+        let client;
+        if timeout <= 0 {
+            client = Client::builder().build().map_err(|err| HttpError::CreateClientError(Box::new(err)))?;
+        } else {
+            client = Client::builder().timeout(Duration::new(timeout, 0)).build().map_err(|err| HttpError::CreateClientError(Box::new(err)))?;
+        }
+
+        Ok(client)
+    }
+
+    /// get response, request from `range_start` if it is not `None`
+    async fn get_response(options: &DownloadOptions, range_start: Option<u64>) -> Result<(Response, String), HttpError> {
         if options.url.is_empty() {
             println!("{} download url is empty !", LOGGER_PREFIX.cyan().bold());
             return Err(HttpError::Empty("download url is empty !".to_string()));
@@ -73,15 +115,13 @@ impl Download {
             return Err(HttpError::Empty("download file name is empty, please check `url` or `file_name` !".to_string()));
         }
 
-        let timeout = Download::get_timeout(&options);
-        let client;
-        if timeout <= 0 {
-            client = Client::builder().build().map_err(|err| HttpError::CreateClientError(Box::new(err)))?;
-        } else {
-            client = Client::builder().timeout(Duration::new(timeout, 0)).build().map_err(|err| HttpError::CreateClientError(Box::new(err)))?;
+        let client = Download::get_client(options)?;
+        let mut request = client.get(options.url.clone());
+        if let Some(range_start) = range_start {
+            request = request.header(RANGE, format!("bytes={}-", range_start));
         }
 
-        let response = client.get(options.url.clone()).send().await.map_err(|err| HttpError::SendError(Box::new(err)))?;
+        let response = request.send().await.map_err(|err| HttpError::SendError(Box::new(err)))?;
         Ok((response, download_file_name))
     }
 
@@ -97,15 +137,124 @@ impl Download {
         return output_file_path;
     }
 
-    /// download file, include progress bar
+    /// get progress bar
+    fn get_progress_bar(progress: Option<&MultiProgress>, total_length: u64, download_file_name: &str) -> ProgressBar {
+        let pb;
+        if progress.is_none() {
+            pb = ProgressBar::new(total_length);
+        } else {
+            let progress = progress.unwrap();
+            pb = progress.add(ProgressBar::new(total_length));
+        }
+
+        let download_file_name = download_file_name.to_string();
+        pb.set_style(
+            ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({msg}) ({eta})")
+                .unwrap()
+                .with_key("msg", move |_state: &ProgressState, w: &mut dyn ProgressWrite| write!(w, "{}", download_file_name).unwrap())
+                .with_key("eta", |state: &ProgressState, w: &mut dyn ProgressWrite| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
+                .progress_chars("#>-"),
+        );
+
+        return pb;
+    }
+
+    /// sleep until the average speed since `start_time` is under `max_kbps`
+    async fn throttle(start_time: Instant, downloaded_size: u64, max_kbps: u64) {
+        if max_kbps == 0 {
+            return;
+        }
+
+        let expected = Duration::from_secs_f64(downloaded_size as f64 / (max_kbps as f64 * 1_000.0));
+        let elapsed = start_time.elapsed();
+        if expected > elapsed {
+            tokio::time::sleep(expected - elapsed).await;
+        }
+    }
+
+    /// check the download is cancelled
+    fn is_cancelled(options: &DownloadOptions) -> bool {
+        return options.cancel.as_ref().map(|cancel| cancel.load(Ordering::SeqCst)).unwrap_or(false);
+    }
+
+    /// download file, include progress bar, then compute the sha256 checksum and verify `expected_sha256`
     pub async fn download(options: DownloadOptions, progress: Option<&MultiProgress>) -> Result<DownloadResult, HttpError> {
+        let output_file_path = Download::get_output_file(&options, &Download::get_file_name(&options)).as_path().to_string_lossy().to_string();
+        let expected_sha256 = options.expected_sha256.clone();
+        let mut result = Download::download_file(options, progress).await?;
+        if !result.success {
+            return Ok(result);
+        }
+
+        result.sha256 = FileHandler::get_file_hash(&output_file_path).map_err(|err| HttpError::Error(err.into()))?;
+        if let Some(expected_sha256) = expected_sha256 {
+            if !result.sha256.eq_ignore_ascii_case(expected_sha256.trim()) {
+                // delete the bad file, so the next download will not skip it
+                let _ = fs::remove_file(&output_file_path);
+                return Err(HttpError::Error(format!("verify file `{}` checksum failed, expected: {}, actual: {}", &output_file_path, expected_sha256, result.sha256).into()));
+            }
+        }
+
+        return Ok(result);
+    }
+
+    /// download file, include progress bar
+    async fn download_file(options: DownloadOptions, progress: Option<&MultiProgress>) -> Result<DownloadResult, HttpError> {
         let mut result = DownloadResult::default();
         result.url = options.url.clone();
         result.dir = options.output_dir.clone().unwrap_or(String::new());
 
-        let (mut response, download_file_name) = Download::get_response(&options).await?;
+        // resume from the partial file
+        let resume = options.resume.unwrap_or(false);
+        let mut resume_size = if resume {
+            fs::metadata(Download::get_output_file(&options, &Download::get_file_name(&options))).map(|metadata| metadata.len()).unwrap_or(0)
+        } else {
+            0
+        };
+
+        let (mut response, download_file_name) = Download::get_response(&options, if resume_size > 0 { Some(resume_size) } else { None }).await?;
         result.file_name = download_file_name.clone();
 
+        if resume_size > 0 {
+            match response.status() {
+                StatusCode::PARTIAL_CONTENT => {
+                    if progress.is_none() {
+                        println!("{} resume file {} from {} bytes", LOGGER_PREFIX.cyan().bold(), &download_file_name, resume_size);
+                    }
+                }
+                StatusCode::RANGE_NOT_SATISFIABLE => {
+                    // `Content-Range: bytes */<total>`, the partial file is already complete
+                    let total_size = response
+                        .headers()
+                        .get(CONTENT_RANGE)
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(|value| value.rsplit('/').next())
+                        .and_then(|value| value.trim().parse::<u64>().ok());
+                    if total_size == Some(resume_size) {
+                        if progress.is_none() {
+                            println!("{} file {} has been downloaded, skip !", LOGGER_PREFIX.cyan().bold(), &download_file_name);
+                        }
+                        result.success = true;
+                        result.resumed_size = resume_size;
+                        return Ok(result);
+                    }
+
+                    // download from scratch
+                    resume_size = 0;
+                    response = Download::get_response(&options, None).await?.0;
+                }
+                _ => {
+                    // server does not support range, download from scratch
+                    if progress.is_none() {
+                        println!("{} server does not support range request, download file {} from scratch", LOGGER_PREFIX.cyan().bold(), &download_file_name);
+                    }
+                    resume_size = 0;
+                }
+            }
+        }
+
+        result.resumed_size = resume_size;
+
         if !response.status().is_success() {
             println!("{} download file {} failed with status code: {}", LOGGER_PREFIX.cyan().bold(), &download_file_name.cyan().bold(), response.status());
             return Ok(result);
@@ -132,7 +281,7 @@ impl Download {
         let mut has_need_download = true;
 
         // judge file is downloaded
-        if output_file_path.exists() {
+        if resume_size == 0 && output_file_path.exists() {
             let size = fs::metadata(&output_file_path).unwrap().len();
             if size == content_length {
                 // download success
@@ -148,7 +297,17 @@ impl Download {
             return Ok(result);
         }
 
-        let file = match File::create(&output_file_path) {
+        // parallel download by `Range` requests
+        let connections = options.connections.unwrap_or(1);
+        let accept_ranges = response.headers().get(ACCEPT_RANGES).and_then(|value| value.to_str().ok()).map(|value| value.trim().eq_ignore_ascii_case("bytes")).unwrap_or(false);
+        if connections > 1 && resume_size == 0 && accept_ranges && response.status() == StatusCode::OK {
+            drop(response);
+            return Download::download_parallel(&options, result, &download_file_name, &output_file_path, content_length, connections, progress).await;
+        }
+
+        // append to the partial file when resuming
+        let file = if resume_size > 0 { OpenOptions::new().append(true).open(&output_file_path) } else { File::create(&output_file_path) };
+        let file = match file {
             Ok(file) => Some(file),
             Err(err) => {
                 println!("{} create file {} error: {:#?}", LOGGER_PREFIX.cyan().bold(), output_file_path.as_path().to_string_lossy().to_string().red().bold(), err);
@@ -162,28 +321,28 @@ impl Download {
 
         let mut file = file.unwrap();
         let mut downloaded_size = 0u64;
-        let mut time = Instant::now();
-        let mut download_speed = 0.0; // 下载速度
+        let start_time = Instant::now();
 
-        let pb;
-        if progress.is_none() {
-            pb = ProgressBar::new(content_length);
-        } else {
-            let progress = progress.unwrap();
-            pb = progress.add(ProgressBar::new(content_length));
-        }
-
-        let download_file_name_clone = download_file_name.clone();
-        pb.set_style(
-            ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({msg}) ({eta})")
-                .unwrap()
-                .with_key("msg", move |_state: &ProgressState, w: &mut dyn ProgressWrite| write!(w, "{}", download_file_name_clone).unwrap())
-                .with_key("eta", |state: &ProgressState, w: &mut dyn ProgressWrite| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
-                .progress_chars("#>-"),
-        );
+        // `content_length` of `206` response is the remaining size
+        let total_length = resume_size + content_length;
+        let pb = Download::get_progress_bar(progress, total_length, &download_file_name);
+        pb.set_position(resume_size);
 
         // download
         while let Some(chunk) = response.chunk().await.unwrap() {
+            if Download::is_cancelled(&options) {
+                pb.abandon_with_message("cancelled");
+                drop(file);
+                if !resume {
+                    let _ = fs::remove_file(&output_file_path);
+                }
+                println!("{} download file {} cancelled !", LOGGER_PREFIX.cyan().bold(), &download_file_name.cyan().bold());
+                result.downloaded_size = downloaded_size;
+                result.duration = start_time.elapsed();
+                result.cancelled = true;
+                return Ok(result);
+            }
+
             let chunk_size = chunk.len() as u64;
             downloaded_size += chunk_size;
 
@@ -199,25 +358,226 @@ impl Download {
                 return Ok(result);
             }
 
-            // calculate download speed
-            let elapsed_time = time.elapsed().as_secs_f64();
-            if elapsed_time >= 1.0 {
-                download_speed = (downloaded_size as f64 / elapsed_time) / 1_000.0; // kbps
-                time = Instant::now();
-            }
-
             // update progress bar
-            pb.set_position(min(downloaded_size, content_length));
+            pb.set_position(min(resume_size + downloaded_size, total_length));
+
+            // throttle
+            if let Some(max_kbps) = options.max_kbps {
+                Download::throttle(start_time, downloaded_size, max_kbps).await;
+            }
         }
 
         pb.finish_with_message(" ");
         file.sync_all().map_err(|err| HttpError::Error(Box::new(err)))?;
 
+        // transfer statistics
+        let duration = start_time.elapsed();
+        result.downloaded_size = downloaded_size;
+        result.duration = duration;
+        result.average_speed = if duration.as_secs_f64() > 0.0 { downloaded_size as f64 / duration.as_secs_f64() } else { downloaded_size as f64 };
+
         if progress.is_none() {
-            println!("{} download file {} successfully !", LOGGER_PREFIX.cyan().bold(), &download_file_name.cyan().bold());
+            println!(
+                "{} download file {} successfully, size: {} bytes, duration: {:.2?}, speed: {:.2} KB/s !",
+                LOGGER_PREFIX.cyan().bold(),
+                &download_file_name.cyan().bold(),
+                result.downloaded_size,
+                result.duration,
+                result.average_speed / 1_000.0
+            );
         }
 
         result.success = true;
         return Ok(result);
     }
+
+    /// download file by parallel `Range` requests, every connection writes its chunk into the offset of the output file
+    async fn download_parallel(options: &DownloadOptions, mut result: DownloadResult, download_file_name: &str, output_file_path: &PathBuf, content_length: u64, connections: u8, progress: Option<&MultiProgress>) -> Result<DownloadResult, HttpError> {
+        let file = File::create(output_file_path).map_err(|err| HttpError::Error(Box::new(err)))?;
+        file.set_len(content_length).map_err(|err| HttpError::Error(Box::new(err)))?;
+        drop(file);
+
+        if progress.is_none() {
+            println!("{} download file {} by {} connections", LOGGER_PREFIX.cyan().bold(), download_file_name, connections);
+        }
+
+        let client = Download::get_client(options)?;
+        let pb = Download::get_progress_bar(progress, content_length, download_file_name);
+        let downloaded_size = Arc::new(AtomicU64::new(0));
+        let start_time = Instant::now();
+
+        // split ranges
+        let chunk_size = (content_length + connections as u64 - 1) / connections as u64;
+        let mut tasks = Vec::new();
+        let mut start = 0;
+        while start < content_length {
+            let end = min(start + chunk_size, content_length) - 1;
+            let range = DownloadRange {
+                client: client.clone(),
+                url: options.url.clone(),
+                output_file_path: output_file_path.clone(),
+                start,
+                end,
+                pb: pb.clone(),
+                downloaded_size: downloaded_size.clone(),
+                cancel: options.cancel.clone(),
+                max_kbps: options.max_kbps,
+                start_time,
+            };
+            tasks.push(tokio::spawn(Download::download_range(range)));
+            start = end + 1;
+        }
+
+        // wait for all connections
+        let mut cancelled = false;
+        let mut errors: Vec<String> = Vec::new();
+        for task in tasks {
+            match task.await {
+                Ok(Ok(completed)) => cancelled = cancelled || !completed,
+                Ok(Err(err)) => errors.push(err),
+                Err(err) => errors.push(format!("download task error: {:#?}", err)),
+            }
+        }
+
+        let size = downloaded_size.load(Ordering::SeqCst);
+        result.downloaded_size = size;
+        result.duration = start_time.elapsed();
+
+        // the file has holes, can not be resumed
+        if cancelled || !errors.is_empty() {
+            let _ = fs::remove_file(output_file_path);
+            if cancelled && errors.is_empty() {
+                pb.abandon_with_message("cancelled");
+                println!("{} download file {} cancelled !", LOGGER_PREFIX.cyan().bold(), download_file_name.cyan().bold());
+                result.cancelled = true;
+                return Ok(result);
+            }
+
+            pb.abandon_with_message("failed");
+            return Err(HttpError::Error(format!("download file `{}` error: {}", download_file_name, errors.join(", ")).into()));
+        }
+
+        pb.finish_with_message(" ");
+        let file_size = fs::metadata(output_file_path).map(|metadata| metadata.len()).unwrap_or(0);
+        if size != content_length || file_size != content_length {
+            let _ = fs::remove_file(output_file_path);
+            return Err(HttpError::Error(
+                format!("download file `{}` size mismatch, expected: {}, downloaded: {}, file: {}", download_file_name, content_length, size, file_size).into(),
+            ));
+        }
+
+        let duration = result.duration;
+        result.average_speed = if duration.as_secs_f64() > 0.0 { size as f64 / duration.as_secs_f64() } else { size as f64 };
+        if progress.is_none() {
+            println!(
+                "{} download file {} successfully, size: {} bytes, duration: {:.2?}, speed: {:.2} KB/s !",
+                LOGGER_PREFIX.cyan().bold(),
+                download_file_name.cyan().bold(),
+                result.downloaded_size,
+                result.duration,
+                result.average_speed / 1_000.0
+            );
+        }
+
+        result.success = true;
+        return Ok(result);
+    }
+
+    /// download `start..=end` bytes into the output file, return `false` if cancelled
+    async fn download_range(range: DownloadRange) -> Result<bool, String> {
+        let response = range
+            .client
+            .get(&range.url)
+            .header(RANGE, format!("bytes={}-{}", range.start, range.end))
+            .send()
+            .await
+            .map_err(|err| format!("request range {}-{} error: {}", range.start, range.end, err))?;
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            return Err(format!("request range {}-{} failed with status code: {}", range.start, range.end, response.status()));
+        }
+
+        let mut file = OpenOptions::new().write(true).open(&range.output_file_path).map_err(|err| format!("open file error: {}", err))?;
+        file.seek(SeekFrom::Start(range.start)).map_err(|err| format!("seek file error: {}", err))?;
+
+        let mut response = response;
+        let mut size = 0u64;
+        let length = range.end - range.start + 1;
+        while let Some(chunk) = response.chunk().await.map_err(|err| format!("read range {}-{} error: {}", range.start, range.end, err))? {
+            if range.cancel.as_ref().map(|cancel| cancel.load(Ordering::SeqCst)).unwrap_or(false) {
+                return Ok(false);
+            }
+
+            // ignore the bytes beyond the range
+            let chunk = &chunk[..min(chunk.len() as u64, length - size) as usize];
+            file.write_all(chunk).map_err(|err| format!("write file error: {}", err))?;
+            size += chunk.len() as u64;
+            range.pb.inc(chunk.len() as u64);
+            let downloaded_size = range.downloaded_size.fetch_add(chunk.len() as u64, Ordering::SeqCst) + chunk.len() as u64;
+
+            // throttle by the total speed of all connections
+            if let Some(max_kbps) = range.max_kbps {
+                Download::throttle(range.start_time, downloaded_size, max_kbps).await;
+            }
+
+            if size >= length {
+                break;
+            }
+        }
+
+        if size != length {
+            return Err(format!("range {}-{} is incomplete, expected: {}, downloaded: {}", range.start, range.end, length, size));
+        }
+
+        file.sync_all().map_err(|err| format!("sync file error: {}", err))?;
+        Ok(true)
+    }
+
+    /// download archive, then extract it into `extract_dir`, the checksum is verified by `download` when `expected_sha256` is set
+    /// support `.zip`、`.tar.gz` and `.tgz`, entries outside `extract_dir` (zip slip) will be rejected
+    pub async fn download_and_extract(options: DownloadOptions, extract_dir: &str) -> Result<DownloadResult, HttpError> {
+        if extract_dir.is_empty() {
+            return Err(HttpError::Empty("extract dir is empty !".to_string()));
+        }
+
+        let output_file_path = Download::get_output_file(&options, &Download::get_file_name(&options));
+        let output_file_path = output_file_path.as_path().to_string_lossy().to_string();
+        let result = Download::download(options, None).await?;
+        if !result.success {
+            return Ok(result);
+        }
+
+        println!("{} extract file {} to {} ...", LOGGER_PREFIX.cyan().bold(), &output_file_path.cyan().bold(), extract_dir);
+        Utils::extract(&output_file_path, extract_dir).map_err(|err| HttpError::Error(err.into()))?;
+        println!("{} extract file {} successfully !", LOGGER_PREFIX.cyan().bold(), &output_file_path.cyan().bold());
+        return Ok(result);
+    }
+
+    /// download file list into one output dir, use shared progress bar
+    /// one failed file does not abort the others, each file has its own result in the same order as `options`
+    pub async fn download_list(options: Vec<DownloadOptions>, output_dir: Option<String>) -> Result<Vec<Result<DownloadResult, HttpError>>, HttpError> {
+        if options.is_empty() {
+            println!("{} download list is empty !", LOGGER_PREFIX.cyan().bold());
+            return Err(HttpError::Empty("download list is empty !".to_string()));
+        }
+
+        let progress = MultiProgress::new();
+        let mut results: Vec<Result<DownloadResult, HttpError>> = Vec::new();
+        for mut option in options {
+            if option.output_dir.is_none() {
+                option.output_dir = output_dir.clone();
+            }
+
+            let url = option.url.clone();
+            let result = Download::download(option, Some(&progress)).await;
+            if let Err(err) = &result {
+                println!("{} download file {} error: {:#?}", LOGGER_PREFIX.cyan().bold(), url.red().bold(), err);
+            }
+
+            results.push(result);
+        }
+
+        let success_count = results.iter().filter(|result| result.as_ref().is_ok_and(|result| result.success)).count();
+        println!("{} download files finished, success: {}, total: {}", LOGGER_PREFIX.cyan().bold(), success_count, results.len());
+        return Ok(results);
+    }
 }
@@ -1,9 +1,11 @@
 pub mod client;
 pub mod download;
+pub mod limiter;
 pub mod options;
 
 use crate::download::{Download, DownloadOptions, DownloadResult};
-use crate::options::HttpError;
+use crate::limiter::RateLimiter;
+use crate::options::{ClientOptions, HttpError};
 use client::HttpClient;
 use colored::*;
 use indicatif::MultiProgress;
@@ -15,6 +17,14 @@ const LOGGER_PREFIX: &str = "[Http Request]: ";
 /// form data options
 pub type HttpFormData = reqwest::blocking::multipart::Form;
 
+/// async multipart form, file parts are streamed, use `HttpClient::stream_file_part` to create
+pub type HttpMultipartForm = reqwest::multipart::Form;
+
+/// init the shared client, such as `pool_max_idle_per_host` and `pool_idle_timeout`
+pub fn init_client(options: ClientOptions) -> Result<(), HttpError> {
+    return HttpClient::init_client(options);
+}
+
 /// send
 /// is_form_submit: use form submit
 pub async fn client_send(opts: Options, is_form_submit: bool) -> Result<HttpResponse, HttpError> {
@@ -23,14 +33,65 @@ pub async fn client_send(opts: Options, is_form_submit: bool) -> Result<HttpResp
     return response;
 }
 
-/// send by form-data
+/// init the global rate limiter, all `client_send` requests will wait for it
+pub fn init_rate_limiter(requests_per_second: f64) -> Result<(), HttpError> {
+    return HttpClient::init_rate_limiter(requests_per_second);
+}
+
+/// send with a shared rate limiter, such as one limiter per third-party api
+pub async fn client_send_with_limiter(opts: Options, is_form_submit: bool, limiter: &RateLimiter) -> Result<HttpResponse, HttpError> {
+    return HttpClient::send_with_limiter(opts, is_form_submit, Some(limiter)).await;
+}
+
+/// send by form-data, use blocking client
+/// prefer `client_send_form_data_async` inside async contexts, otherwise it may panic in a tokio worker
 pub fn client_send_form_data(opts: Options) -> Result<HttpResponse, HttpError> {
     let response: Result<HttpResponse, HttpError> = HttpClient::send_form_data(opts);
     // println!("{} response: {:#?}", LOGGER_PREFIX.cyan().bold(), response);
     return response;
 }
 
+/// send by form-data, `files` (name, file path) will be appended to the form and show the upload progress
+pub fn client_send_form_data_with_progress(opts: Options, files: Vec<(String, String)>, progress: Option<&MultiProgress>) -> Result<HttpResponse, HttpError> {
+    return HttpClient::send_form_data_with_progress(opts, files, progress);
+}
+
+/// send by async form-data, `files` (name, file path) are streamed
+pub async fn client_send_form_data_async(opts: Options, files: Vec<(String, String)>) -> Result<HttpResponse, HttpError> {
+    return HttpClient::send_form_data_async(opts, files).await;
+}
+
+/// send by async multipart, large files will be streamed instead of buffered
+pub async fn client_send_multipart(opts: Options, form: HttpMultipartForm) -> Result<HttpResponse, HttpError> {
+    return HttpClient::send_multipart(opts, form).await;
+}
+
+/// add in-memory bytes as a file part to the form-data, such as a generated zip
+pub fn form_data_bytes(form: HttpFormData, name: &str, bytes: Vec<u8>, file_name: &str, mime: Option<&str>) -> Result<HttpFormData, HttpError> {
+    return HttpClient::form_data_bytes(form, name, bytes, file_name, mime);
+}
+
+/// create a form-data from dir, file parts are keyed by relative path
+pub fn form_data_from_dir(dir: &str) -> Result<HttpFormData, HttpError> {
+    return HttpClient::form_data_from_dir(dir);
+}
+
+/// create an async multipart form from dir, file parts are streamed and keyed by relative path
+pub async fn multipart_from_dir(dir: &str) -> Result<HttpMultipartForm, HttpError> {
+    return HttpClient::multipart_from_dir(dir).await;
+}
+
 /// download
 pub async fn download(options: DownloadOptions, progress: Option<&MultiProgress>) -> Result<DownloadResult, HttpError> {
     return Download::download(options, progress).await;
 }
+
+/// download archive, verify `expected_sha256` if set, then extract it
+pub async fn download_and_extract(options: DownloadOptions, extract_dir: &str) -> Result<DownloadResult, HttpError> {
+    return Download::download_and_extract(options, extract_dir).await;
+}
+
+/// download file list into one output dir, each file has its own result
+pub async fn download_list(options: Vec<DownloadOptions>, output_dir: Option<String>) -> Result<Vec<Result<DownloadResult, HttpError>>, HttpError> {
+    return Download::download_list(options, output_dir).await;
+}
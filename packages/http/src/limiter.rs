@@ -0,0 +1,52 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// token bucket rate limiter, cheap to clone and share between requests
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    requests_per_second: f64,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// create limiter, allow `requests_per_second` requests per second, burst is the same as `requests_per_second`
+    pub fn new(requests_per_second: f64) -> Self {
+        let requests_per_second = if requests_per_second > 0.0 { requests_per_second } else { 1.0 };
+        Self {
+            requests_per_second,
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: requests_per_second.max(1.0),
+                last_refill: Instant::now(),
+            })),
+        }
+    }
+
+    /// wait until a token is available
+    pub async fn acquire(&self) {
+        let capacity = self.requests_per_second.max(1.0);
+        let mut bucket = self.bucket.lock().await;
+
+        loop {
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * self.requests_per_second).min(capacity);
+            bucket.last_refill = now;
+
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                return;
+            }
+
+            // hold the lock while waiting, so waiters are served in order
+            let wait = (1.0 - bucket.tokens) / self.requests_per_second;
+            tokio::time::sleep(Duration::from_secs_f64(wait)).await;
+        }
+    }
+}
@@ -16,6 +16,11 @@ fn test_http_get() {
             method: Some("get".to_string()),
             headers: None,
             timeout: None,
+            raw_response: None,
+            auth: None,
+            proxy: None,
+            retries: None,
+            retry_on_status: None,
         };
         let response: HttpResponse = client_send(options, false).await.unwrap();
         assert_eq!(response.status_code, 200);
@@ -42,6 +47,11 @@ fn test_http_post() {
             method: None,
             headers: None,
             timeout: None,
+            raw_response: None,
+            auth: None,
+            proxy: None,
+            retries: None,
+            retry_on_status: None,
         };
         let response: HttpResponse = client_send(options, false).await.unwrap();
         assert_eq!(response.status_code, 200);
@@ -60,6 +70,11 @@ fn test_http_form_data() {
         method: None,
         headers: None,
         timeout: None,
+        raw_response: None,
+        auth: None,
+        proxy: None,
+        retries: None,
+        retry_on_status: None,
     };
     let response: HttpResponse = client_send_form_data(options).unwrap();
     assert_eq!(response.status_code, 200);
@@ -1,13 +1,19 @@
 //! 图片压缩
 
+use crate::error::CompressorError;
 use crate::factor::Factor;
 use crate::img::Img;
+use crate::manifest::Manifest;
 use colored::Colorize;
 use crossbeam_queue::SegQueue;
 use fs_extra::dir;
+use glob::{MatchOptions, Pattern};
+use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::{HashMap, HashSet};
 use std::ffi::OsStr;
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
 use std::time::Instant;
 use std::{fs, thread};
 
@@ -17,6 +23,14 @@ pub struct Compressor {
     pub destination_path: PathBuf,
     pub thread_count: u32,
     pub image_size: u64,
+    pub max_concurrent_large_images: Option<u32>,
+    pub large_image_size: u64,
+    pub manifest: Option<PathBuf>,
+    pub excludes: Vec<Pattern>,
+    pub output_format: OutputFormat,
+    pub progress: bool,
+    pub skip_optimized: bool,
+    pub svg: bool,
 }
 
 #[derive(Debug)]
@@ -25,7 +39,101 @@ pub struct CompressorArgs {
     pub origin: String,
     pub dest: String,
     pub thread_count: Option<u32>,
-    pub image_size: u64, // 要压缩的图片最小值, 默认为 kb
+    pub image_size: u64,                          // 要压缩的图片最小值, 默认为 kb
+    pub max_concurrent_large_images: Option<u32>, // 大图同时处理的最大个数, 防止内存溢出, 默认不限制
+    pub large_image_size: Option<u64>,            // 大图的最小值, 单位为 kb, 默认为 1024
+    pub manifest: Option<String>,                 // 增量压缩清单文件路径, 只压缩有变化的文件, 默认全量压缩
+    pub excludes: Vec<String>,                    // 排除的文件或目录, glob 格式, 如 `**/thumbnails/**`
+    pub output_format: Option<OutputFormat>,      // 输出格式, 默认同原图片
+    pub progress: Option<bool>,                   // 是否显示进度条, 默认为 false, 每个文件处理完成后都会输出进度日志
+    pub skip_optimized: Option<bool>,             // 是否跳过已经压缩过的文件, 默认为 false, 压缩记录保存在输出目录的 `.images-compressor` 文件中
+    pub svg: Option<bool>,                        // 是否压缩 svg, 默认为 false
+}
+
+/// 输出格式
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    #[default]
+    Original, // 同原图片
+    Avif, // avif, gif 除外, 需要开启 `avif` feature
+    Webp, // webp, gif 除外, 开启 `webp` feature 时为有损压缩
+}
+
+/// 大图并发限制
+struct LargeImageLimiter {
+    max: u32,
+    count: Mutex<u32>,
+    condvar: Condvar,
+}
+
+impl LargeImageLimiter {
+    fn new(max: u32) -> Self {
+        Self {
+            max: max.max(1),
+            count: Mutex::new(0),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// 获取许可, 超过最大个数时等待
+    fn acquire(&self) {
+        let mut count = self.count.lock().unwrap();
+        while *count >= self.max {
+            count = self.condvar.wait(count).unwrap();
+        }
+        *count += 1;
+    }
+
+    /// 释放许可
+    fn release(&self) {
+        let mut count = self.count.lock().unwrap();
+        *count -= 1;
+        self.condvar.notify_one();
+    }
+}
+
+/// 压缩进度, 多线程共享
+struct CompressProgress {
+    processed: AtomicUsize,
+    total: usize,
+    bar: Option<ProgressBar>,
+}
+
+impl CompressProgress {
+    fn new(total: usize, show_bar: bool) -> Self {
+        let bar = if show_bar {
+            let bar = ProgressBar::new(total as u64);
+            bar.set_style(
+                ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} ({percent}%) ({eta})")
+                    .unwrap()
+                    .progress_chars("#>-"),
+            );
+            Some(bar)
+        } else {
+            None
+        };
+
+        Self { processed: AtomicUsize::new(0), total, bar }
+    }
+
+    /// 处理完一个文件, 输出进度
+    fn inc<F>(&self, log_func: Arc<Mutex<F>>)
+    where
+        F: FnMut(&str),
+    {
+        let processed = self.processed.fetch_add(1, Ordering::SeqCst) + 1;
+        let percent = processed as f64 * 100.0 / self.total.max(1) as f64;
+        log(&format!("processed {}/{} ({:.1}%)", processed, self.total, percent), log_func);
+        if let Some(bar) = &self.bar {
+            bar.inc(1);
+        }
+    }
+
+    fn finish(&self) {
+        if let Some(bar) = &self.bar {
+            bar.finish();
+        }
+    }
 }
 
 pub struct CompressorFile {
@@ -37,7 +145,41 @@ pub struct CompressorFile {
     pub relative_path: String, // 相对路径
 }
 
-const FILE_LIST: [&str; 4] = ["jpg", "jpeg", "png", "gif"];
+/// 压缩结果
+#[derive(Clone, Debug, Default)]
+pub struct CompressorResult {
+    pub original_size: u64,         // 原图片总大小, 单位为字节
+    pub compressed_size: u64,       // 压缩后总大小, 单位为字节
+    pub files: Vec<CompressedFile>, // 压缩成功的文件
+    pub skipped: usize,             // 压缩后反而变大, 使用原图片的文件个数
+}
+
+/// 单个文件的压缩结果
+#[derive(Clone, Debug)]
+pub struct CompressedFile {
+    pub relative_path: String, // 相对路径
+    pub output_path: String,   // 输出文件全路径
+    pub original_size: u64,    // 原图片大小
+    pub compressed_size: u64,  // 压缩后大小, 使用原图片时与原图片大小相同
+    pub skipped: bool,         // 压缩后反而变大, 使用原图片
+}
+
+impl CompressorResult {
+    /// 节省的字节数
+    pub fn saved_size(&self) -> u64 {
+        return self.original_size.saturating_sub(self.compressed_size);
+    }
+}
+
+const FILE_LIST: [&str; 5] = ["jpg", "jpeg", "png", "gif", "webp"];
+const SVG_EXTENSION: &str = "svg"; // svg 需要开启 `svg` 参数
+const DEFAULT_LARGE_IMAGE_SIZE: u64 = 1024; // 默认大图最小值 1M
+const OPTIMIZED_FILE_NAME: &str = ".images-compressor"; // 压缩记录文件名, 记录输出文件的 hash 值
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: false,
+    require_literal_separator: false,
+    require_literal_leading_dot: false,
+};
 impl Compressor {
     pub fn new(args: CompressorArgs) -> Compressor {
         let factor = args.factor;
@@ -49,7 +191,42 @@ impl Compressor {
             destination_path: PathBuf::from(args.dest),
             thread_count: if thread_count.is_none() { 1 } else { thread_count.unwrap() },
             image_size: args.image_size,
+            max_concurrent_large_images: args.max_concurrent_large_images,
+            large_image_size: args.large_image_size.unwrap_or(DEFAULT_LARGE_IMAGE_SIZE),
+            manifest: args.manifest.filter(|manifest| !manifest.trim().is_empty()).map(PathBuf::from),
+            excludes: args.excludes.iter().filter_map(|exclude| Pattern::new(exclude).ok()).collect(),
+            output_format: args.output_format.unwrap_or_default(),
+            progress: args.progress.unwrap_or(false),
+            skip_optimized: args.skip_optimized.unwrap_or(false),
+            svg: args.svg.unwrap_or(false),
+        }
+    }
+
+    /// 输出文件是否存在, 转换为 avif/webp 时为转换后的文件, 转换后没有变小时为原后缀的文件
+    fn has_output(&self, file: &CompressorFile) -> bool {
+        let dest_file_path = self.destination_path.join(&file.relative_path);
+        if let Some(convert_extension) = get_convert_extension(&file.extension, self.output_format) {
+            if dest_file_path.with_extension(convert_extension).exists() {
+                return true;
+            }
+        }
+
+        return dest_file_path.exists();
+    }
+
+    /// 判断是否被排除, 同时匹配全路径和相对路径
+    fn is_excluded(&self, path: &PathBuf) -> bool {
+        if self.excludes.is_empty() {
+            return false;
         }
+
+        let relative_path = path.strip_prefix(&self.original_path).unwrap_or(path);
+        self.excludes.iter().any(|pattern| pattern.matches_path_with(path, MATCH_OPTIONS) || pattern.matches_path_with(relative_path, MATCH_OPTIONS))
+    }
+
+    /// 是否为支持压缩的文件
+    fn is_supported(&self, extension: &str) -> bool {
+        return FILE_LIST.contains(&extension) || (self.svg && extension == SVG_EXTENSION);
     }
 
     /// get compress dir file list
@@ -57,6 +234,10 @@ impl Compressor {
         for entry in fs::read_dir(file_path).unwrap() {
             let entry = entry.unwrap();
             let path = entry.path();
+            if self.is_excluded(&path) {
+                continue;
+            }
+
             if path.is_dir() {
                 self.get_origin_file_list(&path, files)
             } else {
@@ -67,7 +248,7 @@ impl Compressor {
                 let file_stem = file_name.clone().replace(&format!(".{}", extension), "");
                 let size = fs::metadata(&path).unwrap().len();
                 if self.image_size == 0 {
-                    if FILE_LIST.contains(&extension) {
+                    if self.is_supported(extension) {
                         files.push(CompressorFile {
                             extension: extension.to_string(),
                             path: path.as_path().to_string_lossy().to_string(),
@@ -82,7 +263,7 @@ impl Compressor {
                 }
 
                 if size > self.image_size * 1024 {
-                    if FILE_LIST.contains(&extension) {
+                    if self.is_supported(extension) {
                         files.push(CompressorFile {
                             extension: extension.to_string(),
                             path: path.as_path().to_string_lossy().to_string(),
@@ -98,7 +279,7 @@ impl Compressor {
     }
 
     /// compress
-    pub fn compress<F>(self, log_func: F) -> Result<bool, String>
+    pub fn compress<F>(self, log_func: F) -> Result<CompressorResult, CompressorError>
     where
         F: FnMut(&str) + Send + 'static,
     {
@@ -107,7 +288,7 @@ impl Compressor {
         if !self.original_path.exists() {
             let msg = format!("original path: {} is not exists", self.original_path.as_path().to_string_lossy().to_string().magenta().bold());
             log(&msg, log_func.clone());
-            return Err(msg.to_string());
+            return Err(CompressorError::Error(msg.to_string()));
         }
 
         log(&format!("Starting compress {} ...", "images".cyan().bold()), log_func.clone());
@@ -120,13 +301,72 @@ impl Compressor {
         if files.len() == 0 {
             let elapsed_time = format!("{:.2?}", start_time.elapsed()).magenta().bold();
             log(&format!("Finished compress {} after {}", "images".cyan().bold(), elapsed_time), log_func.clone());
-            return Err(String::from("original path has no files !"));
+            return Err(CompressorError::Error(String::from("original path has no files !")));
+        }
+
+        // 增量压缩, 过滤掉没有变化的文件
+        let mut manifest: Option<HashMap<String, String>> = None;
+        if let Some(manifest_path) = &self.manifest {
+            let prev_manifest = Manifest::read(manifest_path)?;
+            let mut new_manifest = HashMap::new();
+            let mut changed_files = Vec::new();
+            for file in files {
+                let hash = Manifest::get_file_hash(&PathBuf::from(&file.path))?;
+                let is_unchanged = prev_manifest.get(&file.relative_path) == Some(&hash) && self.has_output(&file);
+                if is_unchanged {
+                    new_manifest.insert(file.relative_path.clone(), hash);
+                } else {
+                    changed_files.push(file);
+                }
+            }
+
+            log(&format!("changed file count: {}", changed_files.len().to_string().cyan().bold()), log_func.clone());
+            files = changed_files;
+            manifest = Some(new_manifest);
+        }
+
+        // 跳过已经压缩过的文件, 文件 hash 与上次压缩输出的 hash 相同时不再压缩, 防止重复压缩导致质量下降
+        let optimized_path = self.destination_path.join(OPTIMIZED_FILE_NAME);
+        let mut optimized: Option<HashMap<String, String>> = None;
+        if self.skip_optimized {
+            let prev_optimized = Manifest::read(&optimized_path)?;
+            let prev_hashes: HashSet<&String> = prev_optimized.values().collect();
+            let mut new_optimized = HashMap::new();
+            let mut unoptimized_files = Vec::new();
+            for file in files {
+                let hash = Manifest::get_file_hash(&PathBuf::from(&file.path))?;
+                if prev_hashes.contains(&hash) {
+                    new_optimized.insert(file.relative_path.clone(), hash);
+                } else {
+                    unoptimized_files.push(file);
+                }
+            }
+
+            log(&format!("optimized file count: {}", new_optimized.len().to_string().cyan().bold()), log_func.clone());
+            files = unoptimized_files;
+            optimized = Some(new_optimized);
+        }
+
+        if files.len() == 0 {
+            if let (Some(manifest_path), Some(manifest)) = (&self.manifest, &manifest) {
+                Manifest::write(manifest_path, manifest)?;
+            }
+
+            if let Some(optimized) = &optimized {
+                Manifest::write(&optimized_path, optimized)?;
+            }
+
+            let elapsed_time = format!("{:.2?}", start_time.elapsed()).magenta().bold();
+            log(&format!("Finished compress {} after {}, no changed files !", "images".cyan().bold(), elapsed_time), log_func.clone());
+            return Ok(CompressorResult::default());
         }
 
         // 删除目录文件
         let dest_dir = &self.destination_path;
-        // 判断 origin 和 dest 目录是否相等, 如果不相等则清空 dest 目录
-        if dest_dir != &self.original_path {
+        // 判断 origin 和 dest 目录是否相等, 如果不相等则清空 dest 目录, 增量压缩或跳过已压缩的文件时保留已压缩的文件
+        if self.manifest.is_some() || self.skip_optimized {
+            fs::create_dir_all(dest_dir)?;
+        } else if dest_dir != &self.original_path {
             log(&format!("clear dest dir: {}", dest_dir.as_path().to_string_lossy().to_string().red().bold()), log_func.clone());
             // 不存在则创建, 存在则清空
             match dir::create(dest_dir, true) {
@@ -135,18 +375,24 @@ impl Compressor {
                     // let err_msg = format!("operate dest dir: {}, error", dest_dir.as_path().to_string_lossy().to_string());
                     let msg = format!("operate dest dir: {} error: {:#?}", dest_dir.as_path().to_string_lossy().to_string().magenta().bold(), err);
                     log(&msg, log_func.clone());
-                    return Err(msg.to_string());
+                    return Err(CompressorError::Error(msg.to_string()));
                 }
             }
         }
 
         // 设置队列
+        let total = files.len();
+        let failed: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+        let compressed: Arc<Mutex<Vec<CompressedFile>>> = Arc::new(Mutex::new(Vec::new()));
+        let compressed_files: Vec<(String, String)> = files.iter().map(|file| (file.relative_path.clone(), file.path.clone())).collect();
         let queue = Arc::new(SegQueue::new());
         for i in files {
             queue.push(i);
         }
 
         let mut handles = Vec::new();
+        let limiter = self.max_concurrent_large_images.map(|max| Arc::new(LargeImageLimiter::new(max)));
+        let progress = Arc::new(CompressProgress::new(total, self.progress));
 
         for _ in 0..self.thread_count {
             let arc_queue = Arc::clone(&queue);
@@ -156,11 +402,23 @@ impl Compressor {
                 destination_path: self.destination_path.clone(),
                 thread_count: self.thread_count.clone(),
                 image_size: self.image_size,
+                max_concurrent_large_images: self.max_concurrent_large_images,
+                large_image_size: self.large_image_size,
+                manifest: self.manifest.clone(),
+                excludes: self.excludes.clone(),
+                output_format: self.output_format,
+                progress: self.progress,
+                skip_optimized: self.skip_optimized,
+                svg: self.svg,
             });
 
             let log_func_cloned = log_func.clone();
+            let failed_cloned = failed.clone();
+            let compressed_cloned = compressed.clone();
+            let limiter_cloned = limiter.clone();
+            let progress_cloned = progress.clone();
             let handle = thread::spawn(move || {
-                process(arc_queue, &*arc_args.clone(), log_func_cloned.clone());
+                process(arc_queue, &*arc_args.clone(), failed_cloned, compressed_cloned, limiter_cloned, progress_cloned, log_func_cloned.clone());
             });
 
             handles.push(handle);
@@ -170,16 +428,77 @@ impl Compressor {
             h.join().unwrap();
         }
 
-        log(&format!("Compress complete {} !", "success".cyan().bold()), log_func.clone());
+        progress.finish();
+
+        let failed_files = failed.lock().unwrap().clone();
+        let failed = failed_files.len();
+
+        // 更新清单, 压缩失败的文件不记录, 下次重新压缩
+        if let (Some(manifest_path), Some(mut manifest)) = (&self.manifest, manifest) {
+            for (relative_path, path) in compressed_files.iter() {
+                if failed_files.contains(relative_path) {
+                    continue;
+                }
+
+                manifest.insert(relative_path.clone(), Manifest::get_file_hash(&PathBuf::from(path))?);
+            }
+
+            Manifest::write(manifest_path, &manifest)?;
+        }
+
+        // 更新压缩记录, 记录输出文件的 hash 值
+        if let Some(mut optimized) = optimized {
+            for compressed_file in compressed.lock().unwrap().iter() {
+                let output_path = PathBuf::from(&compressed_file.output_path);
+                if !output_path.exists() {
+                    continue;
+                }
+
+                let relative_path = output_path.strip_prefix(&self.destination_path).unwrap_or(&output_path).to_string_lossy().to_string();
+                optimized.insert(relative_path, Manifest::get_file_hash(&output_path)?);
+            }
+
+            Manifest::write(&optimized_path, &optimized)?;
+        }
+
         let elapsed_time = format!("{:.2?}", start_time.elapsed()).magenta().bold();
-        log(&format!("Finished compress {} after {}", "images".cyan().bold(), elapsed_time), log_func.clone());
+        if failed == total {
+            log(&format!("Finished compress {} after {}, all files failed !", "images".cyan().bold(), elapsed_time), log_func.clone());
+            return Err(CompressorError::Failed(total));
+        }
+
+        if failed > 0 {
+            log(&format!("Compress complete, {} files failed !", failed.to_string().red().bold()), log_func.clone());
+        } else {
+            log(&format!("Compress complete {} !", "success".cyan().bold()), log_func.clone());
+        }
 
-        Ok(true)
+        let files = compressed.lock().unwrap().clone();
+        let result = CompressorResult {
+            original_size: files.iter().map(|file| file.original_size).sum(),
+            compressed_size: files.iter().map(|file| file.compressed_size).sum(),
+            skipped: files.iter().filter(|file| file.skipped).count(),
+            files,
+        };
+
+        log(
+            &format!("total size: {} bytes -> {} bytes, saved {} bytes", result.original_size, result.compressed_size, result.saved_size().to_string().cyan().bold()),
+            log_func.clone(),
+        );
+        log(&format!("Finished compress {} after {}", "images".cyan().bold(), elapsed_time), log_func.clone());
+        Ok(result)
     }
 }
 
-fn process<F>(queue: Arc<SegQueue<CompressorFile>>, compressor: &Compressor, log_func: Arc<Mutex<F>>)
-where
+fn process<F>(
+    queue: Arc<SegQueue<CompressorFile>>,
+    compressor: &Compressor,
+    failed: Arc<Mutex<Vec<String>>>,
+    compressed: Arc<Mutex<Vec<CompressedFile>>>,
+    limiter: Option<Arc<LargeImageLimiter>>,
+    progress: Arc<CompressProgress>,
+    log_func: Arc<Mutex<F>>,
+) where
     F: FnMut(&str),
 {
     while !queue.is_empty() {
@@ -195,14 +514,33 @@ where
                 let tmp_relative_path = &file.relative_path.replace(&file.file_name, &temp_file_name);
                 let new_dest_tmp_file_path = &compressor.destination_path.join(tmp_relative_path);
 
-                compress(&file_path, &new_dest_path, &new_dest_tmp_file_path, &file, compressor, log_func.clone());
+                // 大图需要获取许可
+                let large_limiter = limiter.as_ref().filter(|_| file.file_size > compressor.large_image_size * 1024);
+                if let Some(large_limiter) = large_limiter {
+                    large_limiter.acquire();
+                }
+
+                let result = compress(&file_path, &new_dest_path, &new_dest_tmp_file_path, &file, compressor, log_func.clone());
+                if let Some(large_limiter) = large_limiter {
+                    large_limiter.release();
+                }
+
+                match result {
+                    Ok(compressed_file) => compressed.lock().unwrap().push(compressed_file),
+                    Err(err) => {
+                        log(&format!("compress file: {} error: {}", &file.relative_path.red().bold(), err), log_func.clone());
+                        failed.lock().unwrap().push(file.relative_path.clone());
+                    }
+                }
+
+                progress.inc(log_func.clone());
             }
         }
     }
 }
 
 /// 转换
-fn compress<F>(origin_file_path: &PathBuf, dest_file_path: &PathBuf, dest_tmp_file_path: &PathBuf, file: &CompressorFile, compressor: &Compressor, log_func: Arc<Mutex<F>>) -> bool
+fn compress<F>(origin_file_path: &PathBuf, dest_file_path: &PathBuf, dest_tmp_file_path: &PathBuf, file: &CompressorFile, compressor: &Compressor, log_func: Arc<Mutex<F>>) -> Result<CompressedFile, CompressorError>
 where
     F: FnMut(&str),
 {
@@ -211,13 +549,11 @@ where
     let extension = &file.extension;
 
     if !(factor.quality >= 0.0 && factor.quality <= 100.0) {
-        log(&format!("please check factor quality: {}", factor.quality()), log_func.clone());
-        return false;
+        return Err(CompressorError::Error(format!("please check factor quality: {}", factor.quality())));
     }
 
     if !(factor.size_ratio >= 0.0 && factor.size_ratio <= 1.0) {
-        log(&format!("please check factor size_ratio: {}", factor.size_ratio()), log_func.clone());
-        return false;
+        return Err(CompressorError::Error(format!("please check factor size_ratio: {}", factor.size_ratio())));
     }
 
     if factor.quality == 0.0 {
@@ -231,46 +567,101 @@ where
     }
 
     let parent = match dest_file_path.parent() {
-        Some(parent) => Some(parent),
-        None => None,
-    };
-
-    if parent.is_none() {
-        log(&format!("get file path: {} parent error!", dest_file_path.as_path().to_string_lossy().to_string()), log_func.clone());
-        return false;
-    }
-
-    let success = match fs::create_dir_all(parent.unwrap()) {
-        Ok(_) => true,
-        Err(err) => {
-            log(&format!("create file path: {} error: {}", dest_file_path.as_path().to_string_lossy().to_string(), err), log_func.clone());
-            false
-        }
+        Some(parent) => parent,
+        None => return Err(CompressorError::Error(format!("get file path: {} parent error!", dest_file_path.as_path().to_string_lossy().to_string()))),
     };
 
-    if !success {
-        return false;
-    }
+    fs::create_dir_all(parent)?;
 
     // println!("generate image path: {}", dest_file_path.as_path().to_string_lossy().to_string());
     // println!("generate tmp image path: {}", dest_tmp_file_path.as_path().to_string_lossy().to_string());
 
     let is_same_dir = &compressor.original_path.as_path().to_string_lossy().to_string() == &compressor.destination_path.as_path().to_string_lossy().to_string();
-    if extension == "png" {
-        Img::compress_png(origin_file_path, factor.quality(), dest_file_path, dest_tmp_file_path, file, is_same_dir, log_func.clone());
+    // 输出文件路径, 是否使用了压缩后的图片
+    let convert_extension = get_convert_extension(extension, compressor.output_format);
+    let (output_file_path, is_compressed) = if extension == SVG_EXTENSION {
+        (dest_file_path.clone(), Img::compress_svg(origin_file_path, dest_file_path, dest_tmp_file_path, file, is_same_dir, log_func.clone())?)
+    } else if convert_extension == Some("avif") {
+        (
+            dest_file_path.with_extension("avif"),
+            compress_avif(origin_file_path, &factor, dest_file_path, dest_tmp_file_path, file, is_same_dir, log_func.clone())?,
+        )
+    } else if convert_extension == Some("webp") {
+        (
+            dest_file_path.with_extension("webp"),
+            compress_webp(origin_file_path, &factor, dest_file_path, dest_tmp_file_path, file, is_same_dir, log_func.clone())?,
+        )
+    } else if extension == "png" {
+        (
+            dest_file_path.clone(),
+            Img::compress_png(origin_file_path, factor.quality(), dest_file_path, dest_tmp_file_path, file, is_same_dir, log_func.clone())?,
+        )
     } else if extension == "gif" {
-        Img::compress_gif(origin_file_path, dest_file_path, dest_tmp_file_path, file, is_same_dir, log_func.clone());
+        (dest_file_path.clone(), Img::compress_gif(origin_file_path, &factor, dest_file_path, dest_tmp_file_path, file, is_same_dir, log_func.clone())?)
     } else {
-        let img_resize = Img::resize(origin_file_path, factor.size_ratio(), log_func.clone());
-        if img_resize.is_none() {
-            return false;
-        }
+        let img_resize = Img::resize_with_factor(origin_file_path, &factor, false)?;
+        (dest_file_path.clone(), Img::compress_jpg(img_resize, &factor, dest_file_path, dest_tmp_file_path, file, is_same_dir, log_func.clone())?)
+    };
+
+    // 没有压缩时, 输出文件为原图片, 不是同一目录时为拷贝后的原图片(原来的后缀)
+    let output_file_path = if is_compressed {
+        output_file_path
+    } else if is_same_dir {
+        PathBuf::from(&file.path)
+    } else {
+        dest_file_path.clone()
+    };
+
+    let output_path = output_file_path.as_path().to_string_lossy().to_string();
+    let compressed_size = if is_compressed { fs::metadata(&output_file_path)?.len() } else { file.file_size };
+    return Ok(CompressedFile {
+        relative_path: file_relative_path.clone(),
+        output_path,
+        original_size: file.file_size,
+        compressed_size,
+        skipped: !is_compressed,
+    });
+}
 
-        let img_resize = img_resize.unwrap();
-        Img::compress_jpg(img_resize, factor.quality(), dest_file_path, file_relative_path, log_func.clone());
+/// 转换后的后缀, 转换为 avif 或 webp 时返回对应的后缀, svg、gif 不转换
+fn get_convert_extension(extension: &str, output_format: OutputFormat) -> Option<&'static str> {
+    if extension == SVG_EXTENSION || extension == "gif" {
+        return None;
     }
 
-    return true;
+    return match output_format {
+        OutputFormat::Avif => Some("avif"),
+        OutputFormat::Webp => Some("webp"),
+        OutputFormat::Original if extension == "webp" => Some("webp"),
+        OutputFormat::Original => None,
+    };
+}
+
+/// 转换为 avif, 输出文件后缀为 `.avif`
+#[cfg(feature = "avif")]
+fn compress_avif<F>(origin_file_path: &PathBuf, factor: &Factor, dest_file_path: &PathBuf, dest_tmp_file_path: &PathBuf, file: &CompressorFile, is_same_dir: bool, log_func: Arc<Mutex<F>>) -> Result<bool, CompressorError>
+where
+    F: FnMut(&str),
+{
+    let img_resize = Img::resize_with_factor(origin_file_path, factor, file.extension == "png")?;
+    return Img::compress_avif(img_resize, factor.quality(), &dest_file_path.with_extension("avif"), &dest_tmp_file_path.with_extension("avif"), file, is_same_dir, log_func.clone());
+}
+
+#[cfg(not(feature = "avif"))]
+fn compress_avif<F>(_origin_file_path: &PathBuf, _factor: &Factor, _dest_file_path: &PathBuf, _dest_tmp_file_path: &PathBuf, _file: &CompressorFile, _is_same_dir: bool, _log_func: Arc<Mutex<F>>) -> Result<bool, CompressorError>
+where
+    F: FnMut(&str),
+{
+    return Err(CompressorError::Error(String::from("output format `avif` requires the `avif` feature !")));
+}
+
+/// 转换为 webp, 输出文件后缀为 `.webp`
+fn compress_webp<F>(origin_file_path: &PathBuf, factor: &Factor, dest_file_path: &PathBuf, dest_tmp_file_path: &PathBuf, file: &CompressorFile, is_same_dir: bool, log_func: Arc<Mutex<F>>) -> Result<bool, CompressorError>
+where
+    F: FnMut(&str),
+{
+    let img_resize = Img::resize_with_factor(origin_file_path, factor, file.extension != "jpg" && file.extension != "jpeg")?;
+    return Img::compress_webp(img_resize, factor.quality(), &dest_file_path.with_extension("webp"), &dest_tmp_file_path.with_extension("webp"), file, is_same_dir, log_func.clone());
 }
 
 /// 记录日志
@@ -0,0 +1,24 @@
+//! custom error
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CompressorError {
+    #[error("{0}")]
+    Error(String),
+
+    #[error("open image `{0}` error: {1}")]
+    Open(String, String),
+
+    #[error("decode image `{0}` error: {1}")]
+    Decode(String, String),
+
+    #[error("encode image `{0}` error: {1}")]
+    Encode(String, String),
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("compress images failed, all {0} files failed !")]
+    Failed(usize),
+}
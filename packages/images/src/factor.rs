@@ -1,9 +1,44 @@
 // ! 设置图片默认值
 
+use std::env;
+
+const DEFAULT_QUALITY: f32 = 80.0; // 默认品质
+const DEFAULT_SIZE_RATIO: f32 = 0.8; // 默认压缩比例
+const DEFAULT_GIF_FRAME_SKIP: u32 = 2; // GIF 默认每隔一帧保留一帧
+const DEFAULT_GIF_DELAY_OFFSET: u16 = 1; // GIF 丢帧时默认增加的帧间隔
+const DEFAULT_QUALITY_ENV: &str = "IMG_DEFAULT_QUALITY"; // 默认品质环境变量
+const DEFAULT_SIZE_RATIO_ENV: &str = "IMG_DEFAULT_SIZE_RATIO"; // 默认压缩比例环境变量
+
+/// JPG 色度抽样
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChromaSubsampling {
+    S444, // 4:4:4, 不抽样, 色彩最好, 体积最大
+    S422, // 4:2:2, 水平方向抽样
+    S420, // 4:2:0, 水平和垂直方向抽样
+}
+
+impl ChromaSubsampling {
+    /// 色度 `像素` 相对亮度像素的大小(宽, 高)
+    pub fn pixel_size(&self) -> (u8, u8) {
+        return match self {
+            ChromaSubsampling::S444 => (1, 1),
+            ChromaSubsampling::S422 => (2, 1),
+            ChromaSubsampling::S420 => (2, 2),
+        };
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Factor {
-    pub quality: f32,    // 品质: 0 - 100
-    pub size_ratio: f32, // 压缩比例: 0 - 1
+    pub quality: f32,                                  // 品质: 0 - 100
+    pub size_ratio: f32,                               // 压缩比例: 0 - 1
+    pub progressive: Option<bool>,                     // JPG 是否为渐进式, 默认为 true, false 时输出兼容性更好的 baseline
+    pub chroma_subsampling: Option<ChromaSubsampling>, // JPG 色度抽样, 默认使用 mozjpeg 的设置
+    pub max_width: Option<u32>,                        // 最大宽度, 按比例缩放到该宽度以内, 不放大, 与 size_ratio 同时设置时取较小的尺寸
+    pub max_height: Option<u32>,                       // 最大高度, 同 max_width
+    pub gif_frame_skip: Option<u32>,                   // GIF 每 n 帧保留一帧, 1 为保留所有帧, 默认为 2
+    pub gif_delay_offset: Option<u16>,                 // GIF 每帧增加的帧间隔(1/100 秒), 丢帧时默认为 1, 不丢帧时默认为 0
+    pub preserve_metadata: Option<bool>,               // JPG 是否保留 EXIF(方向、版权等)、XMP、ICC、IPTC 元数据, 默认为 false, 保留后通常会增加几 KB 到几十 KB
 }
 
 impl Factor {
@@ -15,17 +50,78 @@ impl Factor {
         return self.size_ratio;
     }
 
+    pub fn progressive(&self) -> bool {
+        return self.progressive.unwrap_or(true);
+    }
+
+    pub fn preserve_metadata(&self) -> bool {
+        return self.preserve_metadata.unwrap_or(false);
+    }
+
+    /// GIF 每 n 帧保留一帧, 最小为 1
+    pub fn gif_frame_skip(&self) -> u32 {
+        return self.gif_frame_skip.unwrap_or(DEFAULT_GIF_FRAME_SKIP).max(1);
+    }
+
+    /// GIF 每帧增加的帧间隔, 不丢帧时默认不修改
+    pub fn gif_delay_offset(&self) -> u16 {
+        if let Some(gif_delay_offset) = self.gif_delay_offset {
+            return gif_delay_offset;
+        }
+
+        return if self.gif_frame_skip() > 1 { DEFAULT_GIF_DELAY_OFFSET } else { 0 };
+    }
+
+    /// 计算缩放后的尺寸, 取 size_ratio 和 max_width/max_height 中较小的缩放比例, 不放大
+    pub fn get_resize_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let mut scale = self.size_ratio.min(1.0);
+        if let Some(max_width) = self.max_width.filter(|max_width| *max_width > 0 && width > 0) {
+            scale = scale.min(max_width as f32 / width as f32);
+        }
+
+        if let Some(max_height) = self.max_height.filter(|max_height| *max_height > 0 && height > 0) {
+            scale = scale.min(max_height as f32 / height as f32);
+        }
+
+        let resized_width = ((width as f32 * scale) as u32).max(1);
+        let resized_height = ((height as f32 * scale) as u32).max(1);
+        return (resized_width, resized_height);
+    }
+
+    /// 默认品质, 优先读取环境变量 `IMG_DEFAULT_QUALITY`
     pub fn get_default_quality(&self) -> f32 {
-        return 80.0;
+        return Self::get_env_value(DEFAULT_QUALITY_ENV, 100.0).unwrap_or(DEFAULT_QUALITY);
     }
 
+    /// 默认压缩比例, 优先读取环境变量 `IMG_DEFAULT_SIZE_RATIO`
     pub fn get_default_size_ratio(&self) -> f32 {
-        return 0.8;
+        return Self::get_env_value(DEFAULT_SIZE_RATIO_ENV, 1.0).unwrap_or(DEFAULT_SIZE_RATIO);
+    }
+
+    /// 读取环境变量, 值必须在 (0, max] 之间
+    fn get_env_value(name: &str, max: f32) -> Option<f32> {
+        let value = env::var(name).ok()?;
+        let value = value.trim().parse::<f32>().ok()?;
+        if value > 0.0 && value <= max {
+            return Some(value);
+        }
+
+        return None;
     }
 }
 
 impl Default for Factor {
     fn default() -> Self {
-        Self { quality: 80., size_ratio: 0.8 }
+        Self {
+            quality: DEFAULT_QUALITY,
+            size_ratio: DEFAULT_SIZE_RATIO,
+            progressive: None,
+            chroma_subsampling: None,
+            max_width: None,
+            max_height: None,
+            gif_frame_skip: None,
+            gif_delay_offset: None,
+            preserve_metadata: None,
+        }
     }
 }
@@ -1,10 +1,15 @@
 //! 图片操作
 
 use crate::compressor::{log, CompressorFile};
+use crate::error::CompressorError;
+use crate::factor::Factor;
+use crate::svg::Svg;
 use colored::Colorize;
+#[cfg(not(feature = "webp"))]
+use image::codecs::webp::WebPEncoder;
 use image::imageops::FilterType;
+use image::DynamicImage;
 use imagequant::Attributes;
-use lodepng::decode32_file;
 use mozjpeg::{ColorSpace, Compress, ScanMode};
 use std::fs;
 use std::fs::File;
@@ -14,63 +19,129 @@ use std::sync::{Arc, Mutex};
 
 pub struct Img;
 
+const MEMORY_IMAGE_NAME: &str = "<memory>"; // 内存图片名称, 用于错误信息
+const JPG_METADATA_MARKERS: [u8; 3] = [0xE1, 0xE2, 0xED]; // 保留的 jpg 元数据段: APP1(EXIF、XMP)、APP2(ICC)、APP13(IPTC)
+
+#[cfg(feature = "avif")]
+const AVIF_SPEED: u8 = 6; // avif 编码速度: 1 - 10, 越快压缩率越低
+
 #[derive(Clone)]
 pub struct ImgResized {
-    rgb8: Vec<u8>,
+    pixels: Vec<u8>, // rgb8 或 rgba8 像素
     pub(crate) width: usize,
     pub(crate) height: usize,
+    metadata: Vec<u8>, // 原图 jpg 元数据段, preserve_metadata 为 true 时读取
 }
 
 impl Img {
-    pub fn resize<F>(file_path: &PathBuf, resize_ratio: f32, log_func: Arc<Mutex<F>>) -> Option<ImgResized>
+    pub fn resize(file_path: &PathBuf, resize_ratio: f32) -> Result<ImgResized, CompressorError> {
+        return Self::resize_with_alpha(file_path, resize_ratio, false);
+    }
+
+    /// 缩放图片, with_alpha 为 true 时保留透明通道(rgba8)
+    pub fn resize_with_alpha(file_path: &PathBuf, resize_ratio: f32, with_alpha: bool) -> Result<ImgResized, CompressorError> {
+        return Self::resize_by(file_path, with_alpha, |width, height| ((width as f32 * resize_ratio) as u32, (height as f32 * resize_ratio) as u32));
+    }
+
+    /// 按 factor 缩放图片, 同时限制最大宽高
+    pub fn resize_with_factor(file_path: &PathBuf, factor: &Factor, with_alpha: bool) -> Result<ImgResized, CompressorError> {
+        let mut img_resized = Self::resize_by(file_path, with_alpha, |width, height| factor.get_resize_size(width, height))?;
+        if factor.preserve_metadata() {
+            img_resized.metadata = Self::read_jpg_metadata(&fs::read(file_path)?);
+        }
+
+        return Ok(img_resized);
+    }
+
+    /// 缩放图片, get_size 根据原图宽高返回缩放后的宽高
+    fn resize_by<S>(file_path: &PathBuf, with_alpha: bool, get_size: S) -> Result<ImgResized, CompressorError>
     where
-        F: FnMut(&str),
+        S: Fn(u32, u32) -> (u32, u32),
     {
-        let img = match image::open(file_path) {
-            Ok(img) => Some(img),
-            Err(err) => {
-                log(&format!("open image: {} error: {:#?}", file_path.as_path().to_string_lossy().to_string().red().bold(), err), log_func.clone());
-                None
-            }
-        };
+        let file_path_str = file_path.as_path().to_string_lossy().to_string();
+        let img = image::open(file_path).map_err(|err| CompressorError::Open(file_path_str, format!("{:#?}", err)))?;
+        return Ok(Self::resize_image(img, with_alpha, get_size));
+    }
 
-        if img.is_none() {
-            return None;
+    /// 按 factor 缩放内存中的图片
+    pub fn resize_bytes(input: &[u8], factor: &Factor, with_alpha: bool) -> Result<ImgResized, CompressorError> {
+        let img = image::load_from_memory(input).map_err(|err| CompressorError::Decode(MEMORY_IMAGE_NAME.to_string(), format!("{:#?}", err)))?;
+        let mut img_resized = Self::resize_image(img, with_alpha, |width, height| factor.get_resize_size(width, height));
+        if factor.preserve_metadata() {
+            img_resized.metadata = Self::read_jpg_metadata(input);
         }
 
-        let img = img.unwrap();
-        let width = img.width() as usize;
-        let height = img.height() as usize;
+        return Ok(img_resized);
+    }
 
-        let width = width as f32 * resize_ratio;
-        let height = height as f32 * resize_ratio;
+    fn resize_image<S>(img: DynamicImage, with_alpha: bool, get_size: S) -> ImgResized
+    where
+        S: Fn(u32, u32) -> (u32, u32),
+    {
+        let (width, height) = get_size(img.width(), img.height());
 
-        let resized_img = img.resize(width as u32, height as u32, FilterType::Triangle);
+        let resized_img = img.resize(width, height, FilterType::Triangle);
         let resized_width = resized_img.width() as usize;
         let resized_height = resized_img.height() as usize;
 
-        return Some(ImgResized {
-            rgb8: resized_img.into_rgb8().into_vec(),
+        let pixels = if with_alpha { resized_img.into_rgba8().into_vec() } else { resized_img.into_rgb8().into_vec() };
+        return ImgResized {
+            pixels,
             width: resized_width,
             height: resized_height,
-        });
+            metadata: Vec::new(),
+        };
     }
 
-    /// 压缩 jpg
-    pub fn compress_jpg<F>(img_resized: ImgResized, quality: f32, dest_file_path: &PathBuf, file_relative_path: &str, log_func: Arc<Mutex<F>>) -> bool
+    /// 压缩 jpg, 压缩后不小于原图片时保留原图片, 防止重复压缩导致质量下降
+    pub fn compress_jpg<F>(img_resized: ImgResized, factor: &Factor, dest_file_path: &PathBuf, dest_tmp_file_path: &PathBuf, file: &CompressorFile, is_same_dir: bool, log_func: Arc<Mutex<F>>) -> Result<bool, CompressorError>
     where
         F: FnMut(&str),
     {
+        let compressed = Self::encode_jpg(img_resized, factor, &file.relative_path)?;
+        let mut output_file = File::create(dest_tmp_file_path)?;
+        output_file.write_all(&compressed)?;
+        drop(output_file);
+        return Img::validate_image(dest_tmp_file_path, dest_file_path, file, is_same_dir, "JPG", log_func.clone());
+    }
+
+    /// 压缩内存中的 jpg, 返回压缩后的数据, 压缩后不小于原图片时返回原数据
+    pub fn compress_jpg_bytes(input: &[u8], factor: &Factor) -> Result<Vec<u8>, CompressorError> {
+        let img_resized = Self::resize_bytes(input, factor, false)?;
+        let compressed = Self::encode_jpg(img_resized, factor, MEMORY_IMAGE_NAME)?;
+        return Ok(Self::get_smaller(input, compressed));
+    }
+
+    /// 编码 jpg
+    fn encode_jpg(img_resized: ImgResized, factor: &Factor, file_relative_path: &str) -> Result<Vec<u8>, CompressorError> {
         let target_width = img_resized.width;
         let target_height = img_resized.height;
-        let resized_img_data = img_resized.rgb8;
+        let resized_img_data = img_resized.pixels;
 
         let mut comp = Compress::new(ColorSpace::JCS_RGB); // 创建了一个 JPEG 压缩器, 指定了颜色空间为 RGB
-        comp.set_scan_optimization_mode(ScanMode::Auto); // 设置压缩器的扫描优化模式为自动模式, 扫描优化是 JPEG 压缩中的一种技术，用于提高压缩效率
-        comp.set_quality(quality); // 设置 JPEG 图像的质量
+        if factor.progressive() {
+            comp.set_scan_optimization_mode(ScanMode::Auto); // 设置压缩器的扫描优化模式为自动模式, 扫描优化是 JPEG 压缩中的一种技术，用于提高压缩效率
+        }
+
+        // 色度抽样, 需要在 `Compress::new` 设置默认值之后
+        if let Some(chroma_subsampling) = &factor.chroma_subsampling {
+            let (width, height) = chroma_subsampling.pixel_size();
+            let components = comp.components_mut();
+            if let Some(luma) = components.first_mut() {
+                luma.h_samp_factor = width as i32;
+                luma.v_samp_factor = height as i32;
+            }
+
+            for chroma in components.iter_mut().skip(1) {
+                chroma.h_samp_factor = 1;
+                chroma.v_samp_factor = 1;
+            }
+        }
+
+        comp.set_quality(factor.quality()); // 设置 JPEG 图像的质量
         comp.set_size(target_width, target_height); // 设置输出图像的尺寸
         comp.set_mem_dest(); // 设置输出目标为内存, 压缩后的 JPEG 数据将被写入内存而不是文件
-        comp.set_optimize_scans(true); // 启用扫描优化
+        comp.set_optimize_scans(factor.progressive()); // 渐进式时启用扫描优化, 关闭时不使用渐进式扫描(baseline), 仍保留默认的最大压缩配置
         comp.start_compress();
 
         let mut line = 0;
@@ -83,126 +154,96 @@ impl Img {
         }
         comp.finish_compress();
 
-        let compressed = match comp.data_to_vec() {
-            Ok(compressed) => Some(compressed),
-            Err(_) => {
-                log("compress image error !", log_func.clone());
-                None
-            }
-        };
+        let compressed = comp.data_to_vec().map_err(|_| CompressorError::Encode(file_relative_path.to_string(), String::from("compress image error !")))?;
+        return Ok(Self::insert_jpg_metadata(compressed, &img_resized.metadata));
+    }
 
-        if compressed.is_none() {
-            return false;
+    /// 读取 jpg 元数据段(包含 marker 和长度), 不是 jpg 时返回空
+    fn read_jpg_metadata(input: &[u8]) -> Vec<u8> {
+        let mut metadata: Vec<u8> = Vec::new();
+        if !input.starts_with(&[0xFF, 0xD8]) {
+            return metadata;
         }
 
-        let compressed = compressed.unwrap();
+        let mut index = 2;
+        while index + 4 <= input.len() && input[index] == 0xFF {
+            let marker = input[index + 1];
+            // 到达图像数据, 元数据段都在图像数据之前
+            if marker == 0xDA || marker == 0xD9 {
+                break;
+            }
+
+            let length = u16::from_be_bytes([input[index + 2], input[index + 3]]) as usize;
+            let end = index + 2 + length;
+            if length < 2 || end > input.len() {
+                break;
+            }
 
-        let output_file = match File::create(dest_file_path.clone()) {
-            Ok(file) => Some(file),
-            Err(err) => {
-                log(&format!("create file path: {} error: {:#?}", dest_file_path.as_path().to_string_lossy().to_string(), err), log_func.clone());
-                None
+            if JPG_METADATA_MARKERS.contains(&marker) {
+                metadata.extend_from_slice(&input[index..end]);
             }
-        };
 
-        if output_file.is_none() {
-            return false;
+            index = end;
         }
 
-        let mut output_file = output_file.unwrap();
+        return metadata;
+    }
+
+    /// 写入 jpg 元数据段, 放在 SOI 和 JFIF(APP0) 之后
+    fn insert_jpg_metadata(mut output: Vec<u8>, metadata: &[u8]) -> Vec<u8> {
+        if metadata.is_empty() || !output.starts_with(&[0xFF, 0xD8]) {
+            return output;
+        }
 
-        let flag = match output_file.write_all(&compressed) {
-            Ok(_) => {
-                log(&format!("compress `JPG` file: {} success !", file_relative_path.cyan().bold()), log_func.clone());
-                true
-            }
-            Err(err) => {
-                log(&format!("compress `JPG` file: {} error: {:#?}", file_relative_path.red().bold(), err), log_func.clone());
-                false
-            }
-        };
+        let mut index = 2;
+        if output.len() >= 6 && output[2] == 0xFF && output[3] == 0xE0 {
+            index += 2 + u16::from_be_bytes([output[4], output[5]]) as usize;
+        }
 
-        return flag;
+        let index = index.min(output.len());
+        output.splice(index..index, metadata.iter().cloned());
+        return output;
     }
 
     /// 压缩 png
-    pub fn compress_png<F>(file_path: &PathBuf, quality: f32, dest_file_path: &PathBuf, dest_tmp_file_path: &PathBuf, file: &CompressorFile, is_same_dir: bool, log_func: Arc<Mutex<F>>) -> bool
+    pub fn compress_png<F>(file_path: &PathBuf, quality: f32, dest_file_path: &PathBuf, dest_tmp_file_path: &PathBuf, file: &CompressorFile, is_same_dir: bool, log_func: Arc<Mutex<F>>) -> Result<bool, CompressorError>
     where
         F: FnMut(&str),
     {
-        let bitmap = match decode32_file(file_path) {
-            Ok(bitmap) => Some(bitmap),
-            Err(err) => {
-                log(&format!("open image: {} error: {:#?}", file_path.as_path().to_string_lossy().to_string().red().bold(), err), log_func.clone());
-                None
-            }
-        };
+        let input = fs::read(file_path).map_err(|err| CompressorError::Open(file_path.as_path().to_string_lossy().to_string(), format!("{:#?}", err)))?;
+        let compressed = Self::encode_png(&input, quality, &file.relative_path)?;
+        fs::write(dest_tmp_file_path, &compressed)?;
+        return Img::validate_image(dest_tmp_file_path, dest_file_path, file, is_same_dir, "PNG", log_func.clone());
+    }
 
-        if bitmap.is_none() {
-            return false;
-        }
+    /// 压缩内存中的 png, 返回压缩后的数据, 压缩后不小于原图片时返回原数据
+    pub fn compress_png_bytes(input: &[u8], factor: &Factor) -> Result<Vec<u8>, CompressorError> {
+        let compressed = Self::encode_png(input, factor.quality(), MEMORY_IMAGE_NAME)?;
+        return Ok(Self::get_smaller(input, compressed));
+    }
 
-        let bitmap = bitmap.unwrap();
+    /// 编码 png
+    fn encode_png(input: &[u8], quality: f32, file_relative_path: &str) -> Result<Vec<u8>, CompressorError> {
+        let bitmap = lodepng::decode32(input).map_err(|err| CompressorError::Decode(file_relative_path.to_string(), format!("{:#?}", err)))?;
         let width = bitmap.width;
         let height = bitmap.height;
 
+        let decode_error = |err: imagequant::Error| CompressorError::Decode(file_relative_path.to_string(), format!("{:#?}", err));
+
         let mut attribute = Attributes::new();
-        attribute.set_speed(10).unwrap(); // 设置压缩速度，可以根据需要进行调整
-        attribute.set_quality(quality as u8, 99).unwrap(); // 设置品质最小值和最大值, 默认为 0 - 100
-
-        let img = match attribute.new_image(&*bitmap.buffer, width, height, 0.0) {
-            Ok(img) => Some(img),
-            Err(err) => {
-                log(&format!("handle `PNG` image: {} error: {:#?}", &file.relative_path.red().bold(), err), log_func.clone());
-                None
-            }
-        };
+        attribute.set_speed(10).map_err(decode_error)?; // 设置压缩速度，可以根据需要进行调整
+        attribute.set_quality(quality as u8, 99).map_err(decode_error)?; // 设置品质最小值和最大值, 默认为 0 - 100
 
-        if img.is_none() {
-            return false;
-        }
+        let mut img = attribute.new_image(&*bitmap.buffer, width, height, 0.0).map_err(decode_error)?;
 
-        let mut img = img.unwrap();
         // 执行图像量化
-        let result = match attribute.quantize(&mut img) {
-            Ok(result) => Some(result),
-            Err(err) => {
-                log(&format!("handle `PNG` image: {} error: {:#?}", &file.relative_path.red().bold(), err), log_func.clone());
-                None
-            }
-        };
-
-        if result.is_none() {
-            return false;
-        }
+        let mut result = attribute.quantize(&mut img).map_err(decode_error)?;
 
-        let mut result = result.unwrap();
         // 为后续重新映射启用抖动
-        let flag = match result.set_dithering_level(1.0) {
-            Ok(_) => true,
-            Err(err) => {
-                log(&format!("handle `PNG` image: {} error: {:#?}", &file.relative_path.red().bold(), err), log_func.clone());
-                false
-            }
-        };
+        result.set_dithering_level(1.0).map_err(decode_error)?;
 
-        if !flag {
-            return false;
-        }
+        let (palette, pixels) = result.remapped(&mut img).map_err(decode_error)?;
 
-        let value = match result.remapped(&mut img) {
-            Ok(value) => Some(value),
-            Err(err) => {
-                log(&format!("handle `PNG` image: {} error: {:#?}", &file.relative_path.red().bold(), err), log_func.clone());
-                None
-            }
-        };
-
-        if value.is_none() {
-            return false;
-        }
-
-        let (palette, pixels) = value.unwrap();
         // 遍历像素数据，将每个索引映射到调色板上的RGB颜色，并将RGBA像素添加到rgba_pixels中
         let mut rgba_pixels: Vec<u8> = Vec::new();
         for &index in &pixels {
@@ -222,84 +263,53 @@ impl Img {
             }
         }
 
-        // 创建一个新的PNG图像文件
-        let flag = match lodepng::encode_file(dest_tmp_file_path, &rgba_pixels, width, height, lodepng::ColorType::RGBA, 8) {
-            Ok(_) => true,
-            Err(err) => {
-                log(&format!("regenerate `PNG` image: {} error: {:#?}", &file.relative_path.red().bold(), err), log_func.clone());
-                false
-            }
-        };
-
-        if !flag {
-            return false;
-        }
-
-        return Img::validate_image(dest_tmp_file_path, dest_file_path, file, is_same_dir, "PNG", log_func.clone());
+        // 创建一个新的PNG图像
+        return lodepng::encode_memory(&rgba_pixels, width, height, lodepng::ColorType::RGBA, 8).map_err(|err| CompressorError::Encode(file_relative_path.to_string(), format!("{:#?}", err)));
     }
 
     /// 压缩 gif
-    pub fn compress_gif<F>(file_path: &PathBuf, dest_file_path: &PathBuf, dest_tmp_file_path: &PathBuf, file: &CompressorFile, is_same_dir: bool, log_func: Arc<Mutex<F>>) -> bool
+    pub fn compress_gif<F>(file_path: &PathBuf, factor: &Factor, dest_file_path: &PathBuf, dest_tmp_file_path: &PathBuf, file: &CompressorFile, is_same_dir: bool, log_func: Arc<Mutex<F>>) -> Result<bool, CompressorError>
     where
         F: FnMut(&str),
     {
-        let img = match File::open(file_path) {
-            Ok(img) => Some(img),
-            Err(err) => {
-                log(&format!("open `GIF` image: {} error: {:#?}", file_path.as_path().to_string_lossy().to_string().red().bold(), err), log_func.clone());
-                None
-            }
-        };
+        let input = fs::read(file_path).map_err(|err| CompressorError::Open(file_path.as_path().to_string_lossy().to_string(), format!("{:#?}", err)))?;
+        let compressed = Self::encode_gif(&input, factor, &file.relative_path)?;
+        fs::write(dest_tmp_file_path, &compressed)?;
+        return Img::validate_image(dest_tmp_file_path, dest_file_path, file, is_same_dir, "GIF", log_func.clone());
+    }
 
-        if img.is_none() {
-            return false;
-        }
+    /// 压缩内存中的 gif, 返回压缩后的数据, 压缩后不小于原图片时返回原数据
+    pub fn compress_gif_bytes(input: &[u8], factor: &Factor) -> Result<Vec<u8>, CompressorError> {
+        let compressed = Self::encode_gif(input, factor, MEMORY_IMAGE_NAME)?;
+        return Ok(Self::get_smaller(input, compressed));
+    }
 
-        let img = img.unwrap();
+    /// 编码 gif
+    fn encode_gif(input: &[u8], factor: &Factor, file_relative_path: &str) -> Result<Vec<u8>, CompressorError> {
         let mut options = gif::DecodeOptions::new();
         options.set_color_output(gif::ColorOutput::Indexed);
 
-        let decoder = match options.read_info(img) {
-            Ok(decoder) => Some(decoder),
-            Err(err) => {
-                log(&format!("regenerate `GIF` image: {} error: {:#?}", &file.relative_path.red().bold(), err), log_func.clone());
-                None
-            }
-        };
-
-        if decoder.is_none() {
-            return false;
-        }
-
-        let mut decoder = decoder.unwrap();
+        let mut decoder = options.read_info(input).map_err(|err| CompressorError::Decode(file_relative_path.to_string(), format!("{:#?}", err)))?;
         let screen_width = decoder.width();
         let screen_height = decoder.height();
         let global_pal = decoder.global_palette().unwrap_or_default().to_vec();
 
-        let mut output_file = File::create(dest_tmp_file_path).unwrap();
-        let encoder = match gif::Encoder::new(&mut output_file, screen_width, screen_height, &global_pal) {
-            Ok(encoder) => Some(encoder),
-            Err(err) => {
-                log(&format!("regenerate `GIF` image: {} error: {:#?}", &file.relative_path.red().bold(), err), log_func.clone());
-                None
-            }
-        };
+        let mut output: Vec<u8> = Vec::new();
+        let encode_error = |err: gif::EncodingError| CompressorError::Encode(file_relative_path.to_string(), format!("{:#?}", err));
+        let mut encoder = gif::Encoder::new(&mut output, screen_width, screen_height, &global_pal).map_err(encode_error)?;
 
-        if encoder.is_none() {
-            return false;
-        }
-
-        let mut encoder = encoder.unwrap();
+        let frame_skip = factor.gif_frame_skip();
+        let delay_offset = factor.gif_delay_offset();
         let mut frame_number = 1;
-        while let Some(frame) = decoder.read_next_frame().unwrap() {
-            // 减少帧数（每隔一帧写一个帧）
-            if frame_number % 2 == 0 {
+        while let Some(frame) = decoder.read_next_frame().map_err(|err| CompressorError::Decode(file_relative_path.to_string(), format!("{:#?}", err)))? {
+            // 减少帧数（每 frame_skip 帧写一个帧）
+            if (frame_number - 1) % frame_skip != 0 {
                 frame_number += 1;
                 continue;
             }
 
             let mut new_frame = gif::Frame::default();
-            new_frame.delay = frame.delay + 1; // 设置帧间隔（以1/100秒为单位），根据需要调整
+            new_frame.delay = frame.delay.saturating_add(delay_offset); // 设置帧间隔（以1/100秒为单位）
             new_frame.width = frame.width;
             new_frame.height = frame.height;
             new_frame.dispose = frame.dispose;
@@ -311,81 +321,120 @@ impl Img {
             new_frame.palette = frame.palette.clone();
             new_frame.buffer = frame.buffer.clone();
 
-            let success = match encoder.write_frame(&new_frame) {
-                Ok(_) => true,
-                Err(err) => {
-                    log(&format!("regenerate `GIF` image: {} error: {:#?}", &file.relative_path.red().bold(), err), log_func.clone());
-                    false
-                }
+            encoder.write_frame(&new_frame).map_err(encode_error)?;
+            frame_number += 1;
+        }
+
+        drop(encoder);
+        Ok(output)
+    }
+
+    /// 压缩为 avif, 需要开启 `avif` feature
+    #[cfg(feature = "avif")]
+    pub fn compress_avif<F>(img_resized: ImgResized, quality: f32, dest_file_path: &PathBuf, dest_tmp_file_path: &PathBuf, file: &CompressorFile, is_same_dir: bool, log_func: Arc<Mutex<F>>) -> Result<bool, CompressorError>
+    where
+        F: FnMut(&str),
+    {
+        let encode_error = |err: ravif::Error| CompressorError::Encode(file.relative_path.clone(), format!("{:#?}", err));
+        let encoder = ravif::Encoder::new().with_quality(quality).with_speed(AVIF_SPEED);
+
+        // rgba8 每个像素 4 个字节
+        let has_alpha = img_resized.pixels.len() == img_resized.width * img_resized.height * 4;
+        let encoded = if has_alpha {
+            let pixels: Vec<ravif::RGBA8> = img_resized.pixels.chunks_exact(4).map(|p| ravif::RGBA8::new(p[0], p[1], p[2], p[3])).collect();
+            encoder.encode_rgba(ravif::Img::new(&pixels[..], img_resized.width, img_resized.height)).map_err(encode_error)?
+        } else {
+            let pixels: Vec<ravif::RGB8> = img_resized.pixels.chunks_exact(3).map(|p| ravif::RGB8::new(p[0], p[1], p[2])).collect();
+            encoder.encode_rgb(ravif::Img::new(&pixels[..], img_resized.width, img_resized.height)).map_err(encode_error)?
+        };
+
+        fs::write(dest_tmp_file_path, &encoded.avif_file)?;
+        return Img::validate_image(dest_tmp_file_path, dest_file_path, file, is_same_dir, "AVIF", log_func.clone());
+    }
+
+    /// 压缩为 webp, 开启 `webp` feature 时按品质有损压缩, 否则为无损压缩
+    pub fn compress_webp<F>(img_resized: ImgResized, quality: f32, dest_file_path: &PathBuf, dest_tmp_file_path: &PathBuf, file: &CompressorFile, is_same_dir: bool, log_func: Arc<Mutex<F>>) -> Result<bool, CompressorError>
+    where
+        F: FnMut(&str),
+    {
+        // rgba8 每个像素 4 个字节
+        let has_alpha = img_resized.pixels.len() == img_resized.width * img_resized.height * 4;
+        let width = img_resized.width as u32;
+        let height = img_resized.height as u32;
+
+        #[cfg(feature = "webp")]
+        let encoded = {
+            let encoder = if has_alpha {
+                webp::Encoder::from_rgba(&img_resized.pixels, width, height)
+            } else {
+                webp::Encoder::from_rgb(&img_resized.pixels, width, height)
             };
+            encoder.encode(quality).to_vec()
+        };
 
-            if !success {
-                return false;
-            }
+        #[cfg(not(feature = "webp"))]
+        let encoded = {
+            let _ = quality;
+            let color_type = if has_alpha { image::ColorType::Rgba8 } else { image::ColorType::Rgb8 };
+            let mut encoded: Vec<u8> = Vec::new();
+            WebPEncoder::new_lossless(&mut encoded)
+                .encode(&img_resized.pixels, width, height, color_type)
+                .map_err(|err| CompressorError::Encode(file.relative_path.clone(), format!("{:#?}", err)))?;
+            encoded
+        };
 
-            frame_number += 1;
+        fs::write(dest_tmp_file_path, &encoded)?;
+        return Img::validate_image(dest_tmp_file_path, dest_file_path, file, is_same_dir, "WEBP", log_func.clone());
+    }
+
+    /// 压缩 svg
+    pub fn compress_svg<F>(file_path: &PathBuf, dest_file_path: &PathBuf, dest_tmp_file_path: &PathBuf, file: &CompressorFile, is_same_dir: bool, log_func: Arc<Mutex<F>>) -> Result<bool, CompressorError>
+    where
+        F: FnMut(&str),
+    {
+        let content = fs::read_to_string(file_path).map_err(|err| CompressorError::Open(file_path.as_path().to_string_lossy().to_string(), format!("{:#?}", err)))?;
+        fs::write(dest_tmp_file_path, Svg::minify(&content))?;
+        return Img::validate_image(dest_tmp_file_path, dest_file_path, file, is_same_dir, "SVG", log_func.clone());
+    }
+
+    /// 压缩后不小于原图片时返回原数据
+    fn get_smaller(input: &[u8], compressed: Vec<u8>) -> Vec<u8> {
+        if compressed.len() >= input.len() {
+            return input.to_vec();
         }
 
-        return Img::validate_image(dest_tmp_file_path, dest_file_path, file, is_same_dir, "GIF", log_func.clone());
+        return compressed;
     }
 
     /// 校验图片, 判断压缩后图片是不是大于原图片, 如果大于, 则取消压缩
-    fn validate_image<F>(dest_tmp_file_path: &PathBuf, dest_file_path: &PathBuf, file: &CompressorFile, is_same_dir: bool, name: &str, log_func: Arc<Mutex<F>>) -> bool
+    /// 返回是否使用了压缩后的图片, false 表示使用原图片
+    fn validate_image<F>(dest_tmp_file_path: &PathBuf, dest_file_path: &PathBuf, file: &CompressorFile, is_same_dir: bool, name: &str, log_func: Arc<Mutex<F>>) -> Result<bool, CompressorError>
     where
         F: FnMut(&str),
     {
+        let fs_error = |err: fs_extra::error::Error| CompressorError::Error(format!("regenerate `{}` image: {} error: {:#?}", name, &file.relative_path, err));
+
         // 判断压缩后图片是不是大于原图片, 如果大于, 则取消压缩
-        let tmp_file_size = fs::metadata(dest_tmp_file_path).unwrap().len();
-        let success;
-        if tmp_file_size >= file.file_size {
+        let tmp_file_size = fs::metadata(dest_tmp_file_path)?.len();
+        let is_compressed = tmp_file_size < file.file_size;
+        if !is_compressed {
             log(&format!("regenerate `{}` file size: {} bytes, big", name.cyan().bold(), tmp_file_size.to_string().red().bold()), log_func.clone());
             // 删除临时文件
-            let is_ok = match fs_extra::file::remove(dest_tmp_file_path.as_path().to_string_lossy().to_string()) {
-                Ok(_) => {
-                    log(&format!("compress `{}` file: {} success !", name.cyan().bold(), &file.relative_path.cyan().bold()), log_func.clone());
-                    true
-                }
-                Err(err) => {
-                    log(&format!("regenerate `{}` image: {} error: {:#?}", name.cyan().bold(), &file.relative_path.red().bold(), err), log_func.clone());
-                    false
-                }
-            };
+            fs_extra::file::remove(dest_tmp_file_path.as_path().to_string_lossy().to_string()).map_err(fs_error)?;
 
-            if !is_ok {
-                return false;
-            }
-
-            // 不是同一目录, 需要拷贝原来文件到目录
+            // 不是同一目录, 需要拷贝原来文件到目录, 转换格式(avif、webp)时使用原来的后缀
             if !is_same_dir {
-                success = match fs_extra::file::copy(&file.path, dest_file_path.as_path().to_string_lossy().to_string(), &fs_extra::file::CopyOptions::new()) {
-                    Ok(_) => {
-                        log(&format!("compress `{}` file: {} success !", name.cyan().bold(), &file.relative_path.cyan().bold()), log_func.clone());
-                        true
-                    }
-                    Err(err) => {
-                        log(&format!("regenerate `{}` image: {} error: {:#?}", name.cyan().bold(), &file.relative_path.red().bold(), err), log_func.clone());
-                        false
-                    }
-                };
-            } else {
-                success = true;
+                let origin_dest_file_path = dest_file_path.with_extension(&file.extension);
+                fs_extra::file::copy(&file.path, origin_dest_file_path.as_path().to_string_lossy().to_string(), &fs_extra::file::CopyOptions::new()).map_err(fs_error)?;
             }
         } else {
             // 移动文件
             let mut options = fs_extra::file::CopyOptions::new();
             options = options.overwrite(true);
-            success = match fs_extra::file::move_file(dest_tmp_file_path.as_path().to_string_lossy().to_string(), dest_file_path.as_path().to_string_lossy().to_string(), &options) {
-                Ok(_) => {
-                    log(&format!("compress `{}` file: {} success !", name.cyan().bold(), &file.relative_path.cyan().bold()), log_func.clone());
-                    true
-                }
-                Err(err) => {
-                    log(&format!("regenerate `{}` image: {} error: {:#?}", name.cyan().bold(), &file.relative_path.red().bold(), err), log_func.clone());
-                    false
-                }
-            };
+            fs_extra::file::move_file(dest_tmp_file_path.as_path().to_string_lossy().to_string(), dest_file_path.as_path().to_string_lossy().to_string(), &options).map_err(fs_error)?;
         }
 
-        return success;
+        log(&format!("compress `{}` file: {} success !", name.cyan().bold(), &file.relative_path.cyan().bold()), log_func.clone());
+        Ok(is_compressed)
     }
 }
@@ -1,3 +1,6 @@
 pub mod compressor;
+pub mod error;
 pub mod factor;
 pub mod img;
+pub mod manifest;
+pub mod svg;
@@ -0,0 +1,48 @@
+//! 增量压缩清单, 记录每个文件的 hash 值, 格式与 `sha256sum` 输出一致: `<hash>  <relative path>`
+
+use crate::error::CompressorError;
+use crypto_hash::{hex_digest, Algorithm};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+pub struct Manifest;
+
+impl Manifest {
+    /// 读取清单, 文件不存在时返回空
+    pub fn read(file_path: &Path) -> Result<HashMap<String, String>, CompressorError> {
+        let mut manifest = HashMap::new();
+        if !file_path.exists() {
+            return Ok(manifest);
+        }
+
+        let content = fs::read_to_string(file_path)?;
+        for line in content.lines() {
+            if let Some((hash, relative_path)) = line.split_once("  ") {
+                manifest.insert(relative_path.to_string(), hash.to_string());
+            }
+        }
+
+        Ok(manifest)
+    }
+
+    /// 写入清单, 按相对路径排序
+    pub fn write(file_path: &Path, manifest: &HashMap<String, String>) -> Result<(), CompressorError> {
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut relative_paths: Vec<&String> = manifest.keys().collect();
+        relative_paths.sort();
+
+        let content: String = relative_paths.iter().map(|relative_path| format!("{}  {}\n", manifest[*relative_path], relative_path)).collect();
+        fs::write(file_path, content)?;
+        Ok(())
+    }
+
+    /// 获取文件 hash 值
+    pub fn get_file_hash(file_path: &Path) -> Result<String, CompressorError> {
+        let buffer = fs::read(file_path)?;
+        Ok(hex_digest(Algorithm::SHA256, &buffer))
+    }
+}
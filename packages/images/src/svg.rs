@@ -0,0 +1,181 @@
+//! svg 压缩, 保守处理: 删除注释、元数据、编辑器(inkscape、sodipodi)信息和标签之间多余的空白, 不修改路径等数据
+
+pub struct Svg;
+
+const REMOVED_ELEMENTS: [&str; 1] = ["metadata"]; // 删除的元素
+const EDITOR_PREFIXES: [&str; 4] = ["inkscape:", "sodipodi:", "xmlns:inkscape", "xmlns:sodipodi"]; // 删除的编辑器元素和属性前缀
+const PRESERVE_SPACE_ELEMENTS: [&str; 5] = ["text", "tspan", "textPath", "style", "script"]; // 保留空白的元素
+
+impl Svg {
+    /// 压缩 svg 内容
+    pub fn minify(content: &str) -> String {
+        let mut output = String::with_capacity(content.len());
+        let mut rest = content;
+        let mut skip_depth = 0; // 删除元素的嵌套层数
+        let mut preserve_depth = 0; // 保留空白元素的嵌套层数
+
+        while !rest.is_empty() {
+            // 注释
+            if rest.starts_with("<!--") {
+                rest = Self::skip_after(rest, "-->");
+                continue;
+            }
+
+            // CDATA
+            if rest.starts_with("<![CDATA[") {
+                let end = Self::find_end(rest, "]]>");
+                if skip_depth == 0 {
+                    output.push_str(&rest[..end]);
+                }
+
+                rest = &rest[end..];
+                continue;
+            }
+
+            // xml 声明、DOCTYPE 原样保留, DOCTYPE 中可能包含实体定义
+            if rest.starts_with("<?") || rest.starts_with("<!") {
+                let has_subset = rest.starts_with("<!DOCTYPE") && rest.find('[').is_some_and(|index| index < rest.find('>').unwrap_or(rest.len()));
+                let end = if has_subset { Self::find_end(rest, "]>") } else { Self::find_end(rest, ">") };
+                output.push_str(&rest[..end]);
+                rest = &rest[end..];
+                continue;
+            }
+
+            // 标签
+            if rest.starts_with('<') {
+                let end = Self::find_tag_end(rest);
+                let tag = &rest[..end];
+                rest = &rest[end..];
+
+                let is_closing = tag.starts_with("</");
+                let is_self_closing = tag.ends_with("/>");
+                let name = Self::get_tag_name(tag);
+
+                if skip_depth > 0 {
+                    if !is_self_closing {
+                        skip_depth = if is_closing { skip_depth - 1 } else { skip_depth + 1 };
+                    }
+                    continue;
+                }
+
+                if !is_closing && Self::is_removed_element(name) {
+                    if !is_self_closing {
+                        skip_depth = 1;
+                    }
+                    continue;
+                }
+
+                if PRESERVE_SPACE_ELEMENTS.contains(&name) && !is_self_closing {
+                    preserve_depth = if is_closing { (preserve_depth - 1).max(0) } else { preserve_depth + 1 };
+                }
+
+                if is_closing {
+                    output.push_str(&format!("</{}>", name));
+                } else {
+                    output.push_str(&Self::minify_tag(tag, name, is_self_closing));
+                }
+                continue;
+            }
+
+            // 文本
+            let end = rest.find('<').unwrap_or(rest.len());
+            let text = &rest[..end];
+            rest = &rest[end..];
+            if skip_depth == 0 && (preserve_depth > 0 || !text.trim().is_empty()) {
+                output.push_str(text);
+            }
+        }
+
+        return output;
+    }
+
+    /// 压缩开始标签, 删除编辑器属性和属性之间多余的空白, 属性值不做修改
+    fn minify_tag(tag: &str, name: &str, is_self_closing: bool) -> String {
+        // 标签不完整时原样返回
+        let end = if is_self_closing { tag.len() - 2 } else { tag.len() - 1 };
+        if !tag.ends_with('>') || end < 1 + name.len() {
+            return tag.to_string();
+        }
+
+        let mut attributes = &tag[1 + name.len()..end];
+        let mut output = format!("<{}", name);
+
+        loop {
+            attributes = attributes.trim_start();
+            if attributes.is_empty() {
+                break;
+            }
+
+            // 属性名
+            let name_end = attributes.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(attributes.len());
+            let attribute_name = &attributes[..name_end];
+            let mut value_end = name_end;
+
+            // 属性值
+            let after_name = attributes[name_end..].trim_start();
+            if let Some(value) = after_name.strip_prefix('=') {
+                let value = value.trim_start();
+                let value_start = attributes.len() - value.len();
+                value_end = match value.chars().next() {
+                    Some(quote) if quote == '"' || quote == '\'' => value[1..].find(quote).map_or(attributes.len(), |index| value_start + index + 2),
+                    _ => value.find(char::is_whitespace).map_or(attributes.len(), |index| value_start + index),
+                };
+            }
+
+            let attribute = &attributes[..value_end];
+            attributes = &attributes[value_end..];
+            if EDITOR_PREFIXES.iter().any(|prefix| attribute_name.starts_with(prefix)) {
+                continue;
+            }
+
+            // 属性值内的空白保留, 只去掉 `=` 两边的空白
+            let (attribute_name, attribute_value) = attribute.split_once('=').map_or((attribute, None), |(name, value)| (name.trim(), Some(value.trim())));
+            output.push(' ');
+            output.push_str(attribute_name);
+            if let Some(attribute_value) = attribute_value {
+                output.push('=');
+                output.push_str(attribute_value);
+            }
+        }
+
+        output.push_str(if is_self_closing { "/>" } else { ">" });
+        return output;
+    }
+
+    /// 是否为删除的元素
+    fn is_removed_element(name: &str) -> bool {
+        return REMOVED_ELEMENTS.contains(&name) || EDITOR_PREFIXES.iter().any(|prefix| name.starts_with(prefix));
+    }
+
+    /// 获取标签名
+    fn get_tag_name(tag: &str) -> &str {
+        let tag = tag.trim_start_matches('<').trim_start_matches('/');
+        let end = tag.find(|c: char| c.is_whitespace() || c == '/' || c == '>').unwrap_or(tag.len());
+        return &tag[..end];
+    }
+
+    /// 查找标签结束位置, 忽略属性值中的 `>`
+    fn find_tag_end(content: &str) -> usize {
+        let mut quote: Option<char> = None;
+        for (index, c) in content.char_indices() {
+            match quote {
+                Some(q) if c == q => quote = None,
+                Some(_) => {}
+                None if c == '"' || c == '\'' => quote = Some(c),
+                None if c == '>' => return index + 1,
+                None => {}
+            }
+        }
+
+        return content.len();
+    }
+
+    /// 查找结束符之后的位置, 找不到时返回内容长度
+    fn find_end(content: &str, end: &str) -> usize {
+        return content.find(end).map_or(content.len(), |index| index + end.len());
+    }
+
+    fn skip_after<'a>(content: &'a str, end: &str) -> &'a str {
+        return &content[Self::find_end(content, end)..];
+    }
+}
@@ -2,12 +2,20 @@
 
 use images_compressor::compressor::{Compressor, CompressorArgs};
 use images_compressor::factor::Factor;
+use images_compressor::svg::Svg;
 
 #[test]
 fn test_image_compressor() {
     let factor = Factor {
         quality: 80.0,   // 品质: 0 - 100
         size_ratio: 0.8, // // 压缩比例: 0 - 1
+        progressive: None,
+        chroma_subsampling: None,
+        max_width: None,
+        max_height: None,
+        gif_frame_skip: None,
+        gif_delay_offset: None,
+        preserve_metadata: None,
     };
 
     let args = CompressorArgs {
@@ -16,8 +24,105 @@ fn test_image_compressor() {
         dest: "/usr/local/images/outputs".to_string(),
         thread_count: None,
         image_size: 0,
+        max_concurrent_large_images: None,
+        large_image_size: None,
+        manifest: None,
+        excludes: vec![],
+        output_format: None,
+        progress: None,
+        skip_optimized: None,
+        svg: None,
     };
 
-    let success = Compressor::new(args).compress(|str| {}).unwrap();
-    assert_eq!(success, true)
+    let result = Compressor::new(args).compress(|str| {});
+    assert!(result.is_ok())
+}
+
+#[test]
+fn test_factor_resize_size() {
+    let mut factor = Factor::default();
+    factor.size_ratio = 0.8;
+    assert_eq!(factor.get_resize_size(1000, 500), (800, 400));
+
+    // 取较小的尺寸
+    factor.max_width = Some(400);
+    assert_eq!(factor.get_resize_size(1000, 500), (400, 200));
+
+    // 不放大
+    factor.size_ratio = 1.0;
+    factor.max_width = Some(1920);
+    factor.max_height = Some(1080);
+    assert_eq!(factor.get_resize_size(1000, 500), (1000, 500));
+    assert_eq!(factor.get_resize_size(3840, 1080), (1920, 540));
+}
+
+#[test]
+fn test_factor_gif_frame_skip() {
+    let mut factor = Factor::default();
+    assert_eq!((factor.gif_frame_skip(), factor.gif_delay_offset()), (2, 1));
+
+    // 不丢帧时不修改帧间隔
+    factor.gif_frame_skip = Some(1);
+    assert_eq!((factor.gif_frame_skip(), factor.gif_delay_offset()), (1, 0));
+
+    factor.gif_frame_skip = Some(0);
+    factor.gif_delay_offset = Some(3);
+    assert_eq!((factor.gif_frame_skip(), factor.gif_delay_offset()), (1, 3));
+}
+
+#[test]
+fn test_svg_minify() {
+    let content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- Created with Inkscape -->
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:sodipodi="http://sodipodi.sourceforge.net/DTD/sodipodi-0.dtd"
+     width="24"   height="24" sodipodi:docname="icon.svg">
+  <metadata><rdf:RDF><cc:Work/></rdf:RDF></metadata>
+  <sodipodi:namedview id="base" />
+  <path d="M 0 0 L 10 10" fill="none" />
+  <text x="1"> a <tspan>b</tspan> c</text>
+</svg>
+"#;
+
+    let expected = r#"<?xml version="1.0" encoding="UTF-8"?><svg xmlns="http://www.w3.org/2000/svg" width="24" height="24"><path d="M 0 0 L 10 10" fill="none"/><text x="1"> a <tspan>b</tspan> c</text></svg>"#;
+    assert_eq!(Svg::minify(content), expected);
+}
+
+#[test]
+fn test_skip_optimized_keeps_dest_files() {
+    let dir = std::env::temp_dir().join("images-compressor-skip-optimized");
+    let origin = dir.join("origin");
+    let dest = dir.join("dest");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&origin).unwrap();
+
+    // a.svg 已经是最小的, 第二次压缩时被跳过; b.svg 每次都需要压缩
+    std::fs::write(origin.join("a.svg"), r#"<svg width="1"/>"#).unwrap();
+    std::fs::write(origin.join("b.svg"), "<svg>\n  <!-- b -->\n  <path d=\"M 0 0\" />\n</svg>\n").unwrap();
+
+    let run = || {
+        let args = CompressorArgs {
+            factor: None,
+            origin: origin.to_string_lossy().to_string(),
+            dest: dest.to_string_lossy().to_string(),
+            thread_count: None,
+            image_size: 0,
+            max_concurrent_large_images: None,
+            large_image_size: None,
+            manifest: None,
+            excludes: vec![],
+            output_format: None,
+            progress: None,
+            skip_optimized: Some(true),
+            svg: Some(true),
+        };
+
+        Compressor::new(args).compress(|_| {}).unwrap();
+    };
+
+    run();
+    run();
+
+    assert_eq!(std::fs::read_to_string(dest.join("a.svg")).unwrap(), r#"<svg width="1"/>"#);
+    assert_eq!(std::fs::read_to_string(dest.join("b.svg")).unwrap(), r#"<svg><path d="M 0 0"/></svg>"#);
+    let _ = std::fs::remove_dir_all(&dir);
 }
@@ -3,6 +3,7 @@
 use crate::ecma::EcmaMinifier;
 use colored::Colorize;
 use glob::{glob_with, MatchOptions};
+use handlers::file::FileHandler;
 use lightningcss::printer::PrinterOptions;
 use lightningcss::stylesheet::{ParserOptions, StyleSheet};
 use lightningcss::targets::{Browsers, Targets};
@@ -28,6 +29,37 @@ pub struct Args {
 
     #[serde(rename = "optimizationCss")]
     pub optimization_css: bool, // 是否做 CSS 优化, 如果要优化，会合并多个属性, 并做代码简化
+
+    #[serde(rename = "fileMode")]
+    pub file_mode: Option<u32>, // 压缩后文件权限, 如 0o644, 为空时使用系统默认(umask)
+
+    #[serde(rename = "lineEnding")]
+    pub line_ending: Option<LineEnding>, // 压缩后文件换行符, 默认为 LF, 保证不同平台输出一致
+}
+
+/// 压缩结果, 文件路径列表, 便于 CI 汇总
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct MinimizeResult {
+    pub success: bool,          // 是否找到需要压缩的文件
+    pub processed: Vec<String>, // 压缩成功的文件
+    pub excluded: Vec<String>,  // 匹配 excludes 被排除的文件
+    pub skipped: Vec<String>,   // 跳过的文件, 不支持的后缀或压缩后没有变化
+    pub errored: Vec<String>,   // 压缩失败的文件
+}
+
+/// 单个文件压缩结果
+enum MinifyStatus {
+    Processed,
+    Skipped,
+    Errored,
+}
+
+/// 换行符
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LineEnding {
+    LF,
+    CRLF,
+    Preserve, // 保持压缩结果不变
 }
 
 const DEFAULT_EXCLUDES: [&str; 8] = ["**/*.min.js", "**/*.min.css", "**/*.umd.js", "**/*.common.js", "**/*.esm.js", "**/*.amd.js", "**/*.iife.js", "**/*.cjs.js"];
@@ -36,10 +68,12 @@ const DEFAULT_EXCLUDES: [&str; 8] = ["**/*.min.js", "**/*.min.css", "**/*.umd.js
 const DEFAULT_SUFFIX: [&str; 4] = ["html", "js", "css", "json"];
 
 impl Minimize {
-    pub fn exec<F>(args: &Args, log_func: F) -> bool
+    pub fn exec<F>(args: &Args, log_func: F) -> MinimizeResult
     where
         F: FnMut(&str) + Send,
     {
+        let mut result = MinimizeResult::default();
+
         // dir
         let dir = Path::new(&args.dir);
 
@@ -53,7 +87,7 @@ impl Minimize {
 
         if !dir.exists() {
             Self::log(&format!("minimize dir failed, `{:#?}` not exists !", dir), log_func.clone());
-            return false;
+            return result;
         }
 
         let dir = dir.join("**/*");
@@ -78,12 +112,21 @@ impl Minimize {
                         let exclude_path_str = path.as_path().to_string_lossy().to_string();
                         if excludes.iter().any(|pattern| glob::Pattern::new(pattern).map(|pat| pat.matches_path_with(&path.as_path(), options.clone())).unwrap_or(false)) {
                             Self::log(&format!("exclude path: `{}`", exclude_path_str), log_func.clone());
+                            if path.is_file() {
+                                result.excluded.push(exclude_path_str);
+                            }
+                            continue;
+                        }
+
+                        if !path.is_file() {
                             continue;
                         }
 
                         let file_extension = path.extension().unwrap_or(OsStr::new("")).to_str().unwrap_or("");
-                        if path.is_file() && DEFAULT_SUFFIX.contains(&file_extension) {
+                        if DEFAULT_SUFFIX.contains(&file_extension) {
                             paths.push(path.clone())
+                        } else {
+                            result.skipped.push(exclude_path_str);
                         }
                     }
                 }
@@ -98,16 +141,36 @@ impl Minimize {
 
         if paths.is_empty() {
             Self::log("can not found files !", log_func.clone());
-            return false;
+            return result;
         }
 
         // 开启并行任务
-        Self::par(paths, args, log_func.clone());
-        return true;
+        let status = Self::par(paths, args, log_func.clone());
+        for (path, status) in status {
+            match status {
+                MinifyStatus::Processed => result.processed.push(path),
+                MinifyStatus::Skipped => result.skipped.push(path),
+                MinifyStatus::Errored => result.errored.push(path),
+            }
+        }
+
+        Self::log(
+            &format!(
+                "minimize finished, processed: {}, excluded: {}, skipped: {}, errored: {}",
+                result.processed.len(),
+                result.excluded.len(),
+                result.skipped.len(),
+                result.errored.len()
+            ),
+            log_func.clone(),
+        );
+
+        result.success = true;
+        return result;
     }
 
     // 开启并行任务
-    fn par<F>(paths: Vec<PathBuf>, args: &Args, log_func: Arc<Mutex<F>>)
+    fn par<F>(paths: Vec<PathBuf>, args: &Args, log_func: Arc<Mutex<F>>) -> Vec<(String, MinifyStatus)>
     where
         F: FnMut(&str) + Send,
     {
@@ -115,27 +178,38 @@ impl Minimize {
 
         let pool = ThreadPoolBuilder::new().num_threads(4).stack_size(20 * 1024 * 1024).build().unwrap();
 
-        pool.install(|| {
-            paths.par_iter().for_each(|path| {
-                let result = Self::minify_file(path, args.validate_js, args.optimization_css, log_func.clone());
-                match result {
-                    Ok(_) => {
-                        let path_str = path.to_string_lossy().to_string();
-                        Self::log(&format!("{} Minimize File: {}", "✔".green().bold(), &path_str), log_func.clone());
-                    }
-                    Err(err) => {
-                        Self::log(&format!("minimize path: `{:?}` error: {:#?}", &path, err), log_func.clone());
+        return pool.install(|| {
+            paths
+                .par_iter()
+                .map(|path| {
+                    let path_str = path.to_string_lossy().to_string();
+                    let result = Self::minify_file(path, args, log_func.clone());
+                    match result {
+                        Ok(true) => {
+                            Self::log(&format!("{} Minimize File: {}", "✔".green().bold(), &path_str), log_func.clone());
+                            (path_str, MinifyStatus::Processed)
+                        }
+                        Ok(false) => {
+                            Self::log(&format!("skip path: `{}`, minified content is empty or not changed", &path_str), log_func.clone());
+                            (path_str, MinifyStatus::Skipped)
+                        }
+                        Err(err) => {
+                            Self::log(&format!("minimize path: `{:?}` error: {:#?}", &path, err), log_func.clone());
+                            (path_str, MinifyStatus::Errored)
+                        }
                     }
-                }
-            });
+                })
+                .collect()
         });
     }
 
-    // 压缩代码
-    fn minify_file<F>(path: &PathBuf, validate_js: bool, optimization_css: bool, log_func: Arc<Mutex<F>>) -> io::Result<()>
+    // 压缩代码, 返回 false 表示跳过(压缩后为空或没有变化)
+    fn minify_file<F>(path: &PathBuf, args: &Args, log_func: Arc<Mutex<F>>) -> io::Result<bool>
     where
         F: FnMut(&str),
     {
+        let validate_js = args.validate_js;
+        let optimization_css = args.optimization_css;
         let file_extension = path.extension().unwrap_or(OsStr::new("")).to_str().unwrap_or("");
 
         let mut file = fs::File::open(path)?;
@@ -180,14 +254,51 @@ impl Minimize {
         }
 
         if minified.is_empty() {
-            return Ok(());
+            return Ok(false);
+        }
+
+        let minified = Self::normalize_line_ending(minified, args.line_ending.unwrap_or(LineEnding::LF));
+        if minified.as_slice() == code.as_bytes() {
+            return Ok(false);
         }
 
         let mut file = fs::File::create(path)?;
         file.write_all(&minified)?;
         file.sync_all().unwrap(); // 写入磁盘
         drop(file); // 自动关闭文件
-        Ok(())
+
+        // 设置文件权限
+        if let Some(file_mode) = args.file_mode {
+            FileHandler::set_file_mode(&path.to_string_lossy(), file_mode).map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        }
+
+        Ok(true)
+    }
+
+    /// 统一换行符
+    fn normalize_line_ending(content: Vec<u8>, line_ending: LineEnding) -> Vec<u8> {
+        if line_ending == LineEnding::Preserve {
+            return content;
+        }
+
+        let mut normalized = Vec::with_capacity(content.len());
+        let mut i = 0;
+        while i < content.len() {
+            let byte = content[i];
+            if byte == b'\r' && content.get(i + 1) == Some(&b'\n') {
+                i += 1;
+                continue;
+            }
+
+            if byte == b'\n' && line_ending == LineEnding::CRLF {
+                normalized.push(b'\r');
+            }
+
+            normalized.push(byte);
+            i += 1;
+        }
+
+        normalized
     }
 
     fn get_excludes(excludes: Vec<String>) -> Vec<String> {
@@ -9,8 +9,10 @@ fn test_minimize() {
         excludes: vec![],
         validate_js: false,
         optimization_css: false,
+        file_mode: None,
+        line_ending: None,
     };
 
-    let success = Minimize::exec(&args, |str| {});
-    assert_eq!(success, true);
+    let result = Minimize::exec(&args, |str| {});
+    assert_eq!(result.success, true);
 }
@@ -0,0 +1,107 @@
+//! Redis 连接 - 集群
+//! 需要开启 `cluster` feature, 接口与单机 `Redis` 保持一致
+
+use redis::cluster::{ClusterClient, ClusterConnection};
+use redis::{Commands, ConnectionLike, ErrorKind, RedisError, RedisResult};
+
+pub struct RedisCluster {
+    client: ClusterClient,
+}
+
+impl RedisCluster {
+    /// 初始化函数, nodes 为种子节点 url, 如 `redis://127.0.0.1:7000`
+    /// url 格式错误或节点的用户名密码不一致时返回 None
+    pub fn new(nodes: Vec<String>) -> Option<RedisCluster> {
+        if nodes.is_empty() {
+            println!("nodes is empty !");
+            return None;
+        }
+
+        return match ClusterClient::new(nodes) {
+            Ok(client) => Some(RedisCluster { client }),
+            Err(err) => {
+                println!("create redis cluster client failed, error: {:?}", err);
+                None
+            }
+        };
+    }
+
+    /// 连接 Redis 集群, 返回 ClusterConnection
+    pub fn connect(&self) -> Option<ClusterConnection> {
+        return match self.client.get_connection() {
+            Ok(connect) => Some(connect),
+            Err(err) => {
+                println!("connect to redis cluster failed, error: {:?}", err);
+                None
+            }
+        };
+    }
+
+    /// 根据 key 获取数据
+    pub fn get_data<T: redis::FromRedisValue>(&self, connect: &mut Option<ClusterConnection>, key: &str) -> Option<T> {
+        if key.is_empty() {
+            println!("key is null .");
+            return None;
+        }
+
+        return match self.exec(connect, |connection| connection.get(key)) {
+            Ok(value) => Some(value),
+            Err(error) => {
+                println!("get key: {} error: {:?}", key, error);
+                None
+            }
+        };
+    }
+
+    /// 设置值
+    pub fn set_data(&self, connect: &mut Option<ClusterConnection>, key: &str, value: &str) -> bool {
+        if key.is_empty() {
+            println!("key is null .");
+            return false;
+        }
+
+        if value.is_empty() {
+            println!("value is null .");
+            return false;
+        }
+
+        return match self.exec(connect, |connection| connection.set::<&str, &str, String>(key, value)) {
+            Ok(_) => true,
+            Err(err) => {
+                println!("set key: {} error: {:?}", key, err);
+                false
+            }
+        };
+    }
+
+    /// 删除 key, key 不存在时返回 false
+    pub fn del(&self, connect: &mut Option<ClusterConnection>, key: &str) -> bool {
+        if key.is_empty() {
+            println!("key is null .");
+            return false;
+        }
+
+        return match self.exec(connect, |connection| connection.del::<&str, usize>(key)) {
+            Ok(count) => count > 0,
+            Err(err) => {
+                println!("del key: {} error: {:?}", key, err);
+                false
+            }
+        };
+    }
+
+    /// 执行命令, 连接为空或已断开时重新连接
+    fn exec<T, C>(&self, connect: &mut Option<ClusterConnection>, mut command: C) -> RedisResult<T>
+    where
+        C: FnMut(&mut ClusterConnection) -> RedisResult<T>,
+    {
+        let is_open = connect.as_ref().map(|connection| connection.is_open()).unwrap_or(false);
+        if !is_open {
+            println!("redis cluster connection is closed, reconnect ...");
+            *connect = self.connect();
+        }
+
+        let connection = connect.as_mut().ok_or(RedisError::from((ErrorKind::ClientError, "client is null .")))?;
+        return command(connection);
+    }
+}
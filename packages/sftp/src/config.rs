@@ -1,5 +1,10 @@
 //! 配置
 
+use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+
 /// 服务器配置
 #[derive(Debug, Default, Clone)]
 pub struct Server {
@@ -7,30 +12,145 @@ pub struct Server {
     pub port: u32,
     pub username: String,
     pub password: String,
-    pub timeout: Option<u64>,
+    pub timeout: Option<u64>,           // tcp 连接超时时间(秒), 默认为 10
+    pub auth_timeout: Option<u64>,      // ssh 握手和认证超时时间(秒), 默认同 timeout
+    pub operation_timeout: Option<u64>, // 连接成功后 ssh 操作超时时间(秒), 默认不限制
+    pub proxy: Option<Socks5Proxy>,     // socks5 代理, 为空时直连
+    pub compress: Option<bool>,         // 是否开启 ssh 压缩, 慢速网络下可提升传输速度, 默认不开启
+    pub ciphers: Option<String>,        // 加密算法偏好, 逗号分隔, 如 `aes128-ctr,aes256-ctr`, 为空时使用默认
+    pub kex: Option<String>,            // 密钥交换算法偏好, 逗号分隔, 为空时使用默认
+    pub private_key: Option<String>,    // 私钥路径, 设置后使用公钥认证, 否则使用密码认证
+    pub public_key: Option<String>,     // 公钥路径, 为空时由私钥推导
+    pub passphrase: Option<String>,     // 私钥密码
+    pub retries: Option<u32>,           // 连接失败时的尝试次数, 默认为 1, 不重试
+    pub retry_delay_secs: Option<u64>,  // 重试间隔(秒), 默认为 3
+    pub jump: Option<Box<Server>>,      // 跳板机, 先连接跳板机再转发到目标服务器
+    pub host_key_check: bool,           // 是否使用 `~/.ssh/known_hosts` 校验服务器公钥, 默认不校验
 }
 
 impl Server {
     pub fn is_empty(&self) -> bool {
-        return self.host.is_empty() || self.port == 0 || self.username.is_empty() || self.password.is_empty();
+        return self.host.is_empty() || self.port == 0 || self.username.is_empty() || (self.password.is_empty() && self.get_private_key().is_none());
+    }
+
+    /// 私钥路径, 空字符串视为未设置
+    pub fn get_private_key(&self) -> Option<&str> {
+        return self.private_key.as_deref().map(|private_key| private_key.trim()).filter(|private_key| !private_key.is_empty());
     }
 }
 
+/// socks5 代理配置
+#[derive(Debug, Default, Clone)]
+pub struct Socks5Proxy {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>, // 用户名, 为空时不认证
+    pub password: Option<String>,
+}
+
 /// 文件上传配置
 #[derive(Debug, Default, Clone)]
 pub struct Upload {
-    pub cmds: Vec<String>,                // 服务端命令
+    pub cmds: Vec<String>,                // 发布完成后在服务端按顺序执行的命令, 如重启 nginx
     pub dir: String,                      // 目录 或 文件 名称
     pub server_dir: String,               // 上传服务器目录
     pub server_file_name: Option<String>, // 服务端文件名称, 如果是文件默认同文件名, 如果是目录，默认同目录名
+    pub server_temp_dir: Option<String>,  // 远程临时目录, 每次上传在其中创建单独的子目录, 默认为 server_dir 父目录下的 `__SFTP_TEMP_DIR__`
     pub need_increment: bool,             // 是否增量发布, 如果是增量则需要比较文件是否一致
     pub need_delete_dir: Option<bool>,    // 上传结束后是否删除 dir 目录, 默认为 true
+    pub need_parallel_zip: Option<bool>,  // 是否并行压缩, 适用于文件较多的大目录, 默认为 false
+    pub compression: CompressionFormat,   // 压缩格式, 默认为 zip(不压缩内容), 慢速网络可使用 tar.gz
+    pub unzip_retries: Option<u32>,       // 远程解压失败时的尝试次数, 用于 NFS 等文件未及时刷新的情况, 默认为 3
+    pub keep_temp: bool,                  // 是否保留远程 zip 包和解压目录, 用于排查问题, 默认删除
+    pub keep_backup: bool,                // 全量发布时是否保留原目录的备份 `<dir>.bak-<时间>`, 默认发布成功后删除
+    pub dry_run: bool,                    // 只计算发布命令不执行, 保留远程临时目录, 用于检查增量发布
+    pub perm: Option<i32>,                // 远程文件权限, 如 0o644, 默认为 0o777
+    pub dir_perm: Option<i32>,            // 远程目录权限, 如 0o755, 默认为 0o777
+    pub preserve_mtime: bool,             // 是否保留本地文件的修改时间, 默认使用上传时间
+    pub excludes: Vec<String>,            // 排除的文件或目录, glob 格式, 相对 dir 匹配且忽略大小写, 如 `**/*.map`、`**/node_modules`
+    pub includes: Vec<String>,            // 只上传匹配的文件, glob 格式, 为空时上传全部
 }
 
 impl Upload {
     pub fn is_empty(&self) -> bool {
         return self.dir.is_empty() || self.server_dir.is_empty();
     }
+
+    /// 远程文件和目录权限
+    pub fn get_remote_perm(&self) -> RemotePerm {
+        let default = RemotePerm::default();
+        return RemotePerm {
+            file: self.perm.unwrap_or(default.file),
+            dir: self.dir_perm.unwrap_or(default.dir),
+        };
+    }
+
+    /// 是否设置了权限
+    pub fn has_perm(&self) -> bool {
+        return self.perm.is_some() || self.dir_perm.is_some();
+    }
+}
+
+/// 上传压缩格式
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum CompressionFormat {
+    #[default]
+    Zip, // 服务器使用 `unzip` 解压
+    TarGz, // 服务器使用 `tar -xzf` 解压
+}
+
+impl CompressionFormat {
+    /// 文件后缀
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CompressionFormat::Zip => "zip",
+            CompressionFormat::TarGz => "tar.gz",
+        }
+    }
+}
+
+/// 远程文件和目录权限
+#[derive(Debug, Clone, Copy)]
+pub struct RemotePerm {
+    pub file: i32, // 文件权限
+    pub dir: i32,  // 目录权限
+}
+
+impl Default for RemotePerm {
+    fn default() -> Self {
+        Self { file: 0o777, dir: 0o777 }
+    }
+}
+
+/// 文件上传配置
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FileUploadOptions {
+    pub perm: RemotePerm,     // 远程文件和目录权限
+    pub preserve_mtime: bool, // 是否保留本地文件的修改时间
+}
+
+/// ps 命令配置
+#[derive(Debug, Clone)]
+pub struct PsConfig {
+    pub command: String,           // ps 命令, 默认为 `ps aux`, BusyBox 可使用 `ps`
+    pub pid_column: Option<usize>, // pid 所在列(从 0 开始), 为空时根据表头 `PID` 查找, 找不到则默认为 1
+}
+
+impl Default for PsConfig {
+    fn default() -> Self {
+        Self {
+            command: String::from("ps aux"),
+            pid_column: None,
+        }
+    }
+}
+
+/// 程序监控配置
+#[derive(Debug, Default, Clone)]
+pub struct SupervisorConfig {
+    pub restart_on_exit: Option<u32>,    // 程序以非 0 退出码退出时最多重启次数, 为空时不重启
+    pub restart_delay: Option<Duration>, // 第一次重启前的等待时间, 默认 1 秒, 之后每次翻倍
+    pub stop: Option<Arc<AtomicBool>>,   // 设置为 true 时结束监控
 }
 
 // 校验拷贝文件
@@ -51,9 +171,22 @@ impl ValidateCopy {
 #[derive(Debug, Default, Clone)]
 pub struct SftpUploadResult {
     pub host: String,
-    pub file_count: u64,            // 发布的文件个数
-    pub file_list: Vec<String>,     // 发布的文件集合, 只有增量发布时才有值
-    pub exec_commands: Vec<String>, // 执行的命令集
-    pub delete_file_count: u64,     // 删除的文件个数
-    pub need_increment: bool,       // 是否增量发布
+    pub file_count: u64,                    // 发布的文件个数
+    pub file_list: Vec<String>,             // 发布的文件集合, 只有增量发布时才有值
+    pub exec_commands: Vec<String>,         // 执行的命令集
+    pub delete_file_count: u64,             // 删除的文件个数
+    pub changed_files: Vec<String>,         // 新增和修改的文件, 相对服务器目录的路径, 只有增量发布时才有值
+    pub deleted_files: Vec<String>,         // 删除的文件, 相对服务器目录的路径, 只有增量发布时才有值
+    pub need_increment: bool,               // 是否增量发布
+    pub dry_run: bool,                      // 是否为 dry run, 为 true 时 exec_commands 未执行
+    pub backup_dir: Option<String>,         // 全量发布时原目录的备份, 只有保留备份或未执行时才有值
+    pub timings: HashMap<String, Duration>, // 各阶段耗时: compress、connect、upload、unzip、compare、exec、cmds
+}
+
+/// 本地目录比较结果, 均为相对路径
+#[derive(Debug, Default, Clone)]
+pub struct LocalDirDiff {
+    pub added: Vec<String>,   // 新增的文件
+    pub changed: Vec<String>, // 修改的文件, 包含文件名 hash 变化的文件
+    pub removed: Vec<String>, // 删除的文件
 }
@@ -0,0 +1,127 @@
+//! 跳板机, 先连接跳板机, 再通过 direct-tcpip 通道转发到目标服务器
+//! ssh session 只接受 tcp 连接, 所以在本地监听一个端口, 由后台线程在本地连接和通道之间转发数据
+
+use crate::config::Server;
+use crate::sftp::SftpHandler;
+use handlers::error::Error;
+use log::{error, info};
+use ssh2::{Channel, Session};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+pub struct JumpHost;
+
+const BUFFER_SIZE: usize = 32 * 1024;
+const IDLE_INTERVAL: Duration = Duration::from_millis(2); // 没有数据时的等待间隔
+
+impl JumpHost {
+    /// 通过跳板机连接目标地址, 返回本地转发的 tcp 连接
+    /// 目标 session 关闭后转发线程会自动断开跳板机 session
+    pub fn connect<F>(jump: &Server, host: &str, port: u16, log_func: Arc<Mutex<F>>) -> Result<TcpStream, String>
+    where
+        F: FnMut(&str),
+    {
+        SftpHandler::log_info(&format!("connect jump host {}:{} ..", &jump.host, jump.port), log_func.clone());
+        let session = SftpHandler::connect(jump, log_func.clone())?;
+
+        SftpHandler::log_info(&format!("open direct-tcpip channel to {}:{} ..", host, port), log_func.clone());
+        let channel = session.channel_direct_tcpip(host, port, None).map_err(|err| {
+            let msg = format!("open channel to {}:{} by jump host {} error: {:#?}", host, port, &jump.host, err);
+            error!("{}", &msg);
+            Error::convert_string(&msg)
+        })?;
+
+        // 本地转发
+        let listener = TcpListener::bind("127.0.0.1:0").map_err(|err| Self::error("bind local forward port", err))?;
+        let local_address = listener.local_addr().map_err(|err| Self::error("get local forward address", err))?;
+        let tcp = TcpStream::connect(local_address).map_err(|err| Self::error("connect local forward port", err))?;
+        let tcp_address = tcp.local_addr().map_err(|err| Self::error("get local address", err))?;
+
+        // 只接受自己的连接
+        let stream = loop {
+            let (stream, address) = listener.accept().map_err(|err| Self::error("accept local forward connection", err))?;
+            if address == tcp_address {
+                break stream;
+            }
+        };
+
+        thread::spawn(move || Self::forward(session, channel, stream));
+        Ok(tcp)
+    }
+
+    /// 在本地连接和通道之间转发数据, 任意一端关闭后结束
+    fn forward(session: Session, mut channel: Channel, mut stream: TcpStream) {
+        session.set_blocking(false);
+        if let Err(err) = stream.set_nonblocking(true) {
+            error!("set local forward stream nonblocking error: {:#?}", err);
+            return;
+        }
+
+        let mut buffer = [0u8; BUFFER_SIZE];
+        loop {
+            let mut idle = true;
+
+            // 本地 -> 跳板机
+            match stream.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(size) => {
+                    idle = false;
+                    if Self::write_all(&mut channel, &buffer[..size]).is_err() {
+                        break;
+                    }
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(_) => break,
+            }
+
+            // 跳板机 -> 本地
+            match channel.read(&mut buffer) {
+                Ok(0) => {
+                    if channel.eof() {
+                        break;
+                    }
+                }
+                Ok(size) => {
+                    idle = false;
+                    if Self::write_all(&mut stream, &buffer[..size]).is_err() {
+                        break;
+                    }
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(_) => break,
+            }
+
+            if idle {
+                thread::sleep(IDLE_INTERVAL);
+            }
+        }
+
+        session.set_blocking(true);
+        let _ = channel.close();
+        let _ = SftpHandler::close_session(session);
+        info!("jump host forward closed !");
+    }
+
+    /// 非阻塞写入全部数据
+    fn write_all<W: Write>(writer: &mut W, mut data: &[u8]) -> std::io::Result<()> {
+        while !data.is_empty() {
+            match writer.write(data) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(size) => data = &data[size..],
+                Err(err) if err.kind() == ErrorKind::WouldBlock => thread::sleep(IDLE_INTERVAL),
+                Err(err) => return Err(err),
+            }
+        }
+
+        writer.flush().or_else(|err| if err.kind() == ErrorKind::WouldBlock { Ok(()) } else { Err(err) })
+    }
+
+    fn error(action: &str, err: std::io::Error) -> String {
+        let msg = format!("jump host {} error: {:#?}", action, err);
+        error!("{}", &msg);
+        Error::convert_string(&msg)
+    }
+}
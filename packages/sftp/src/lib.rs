@@ -1,5 +1,7 @@
 //! sftp 远程操作
 pub mod config;
+pub mod jump;
+pub mod proxy;
 pub mod runnable;
 pub mod sftp;
 pub mod upload;
@@ -0,0 +1,150 @@
+//! socks5 代理, 通过代理建立 tcp 连接后交给 ssh session 使用
+
+use crate::config::Socks5Proxy;
+use handlers::error::Error;
+use log::error;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+pub struct Socks5;
+
+const SOCKS_VERSION: u8 = 0x05;
+const AUTH_NONE: u8 = 0x00;
+const AUTH_PASSWORD: u8 = 0x02;
+const AUTH_NO_ACCEPTABLE: u8 = 0xff;
+const CMD_CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+impl Socks5 {
+    /// 通过 socks5 代理连接目标地址
+    pub fn connect(proxy: &Socks5Proxy, host: &str, port: u16, timeout: Duration) -> Result<TcpStream, String> {
+        let proxy_address = format!("{}:{}", &proxy.host, proxy.port);
+        let socket = proxy_address.to_socket_addrs().ok().and_then(|mut addrs| addrs.next()).ok_or_else(|| {
+            let msg = format!("resolve socks5 proxy address {} error !", &proxy_address);
+            error!("{}", &msg);
+            Error::convert_string(&msg)
+        })?;
+
+        let mut tcp = TcpStream::connect_timeout(&socket, timeout).map_err(|err| {
+            let msg = if err.kind() == ErrorKind::TimedOut {
+                format!("connect to socks5 proxy {} timeout after {:#?}", &proxy_address, timeout)
+            } else {
+                format!("connect to socks5 proxy {} error: {:#?}", &proxy_address, err)
+            };
+            error!("{}", &msg);
+            Error::convert_string(&msg)
+        })?;
+
+        // 握手期间设置超时, 结束后恢复
+        tcp.set_read_timeout(Some(timeout)).map_err(|err| Self::error("set socks5 read timeout", err))?;
+        tcp.set_write_timeout(Some(timeout)).map_err(|err| Self::error("set socks5 write timeout", err))?;
+
+        Self::auth(&mut tcp, proxy)?;
+        Self::request_connect(&mut tcp, host, port)?;
+
+        tcp.set_read_timeout(None).map_err(|err| Self::error("set socks5 read timeout", err))?;
+        tcp.set_write_timeout(None).map_err(|err| Self::error("set socks5 write timeout", err))?;
+        Ok(tcp)
+    }
+
+    /// 协商认证方式, 有用户名时使用用户名密码认证
+    fn auth(tcp: &mut TcpStream, proxy: &Socks5Proxy) -> Result<(), String> {
+        let username = proxy.username.clone().unwrap_or(String::new());
+        let password = proxy.password.clone().unwrap_or(String::new());
+        let method = if username.is_empty() { AUTH_NONE } else { AUTH_PASSWORD };
+
+        tcp.write_all(&[SOCKS_VERSION, 1, method]).map_err(|err| Self::error("send socks5 greeting", err))?;
+        let mut response = [0u8; 2];
+        tcp.read_exact(&mut response).map_err(|err| Self::error("read socks5 greeting", err))?;
+
+        if response[0] != SOCKS_VERSION || response[1] == AUTH_NO_ACCEPTABLE || response[1] != method {
+            let msg = format!("socks5 proxy does not accept auth method: {}", method);
+            error!("{}", &msg);
+            return Err(Error::convert_string(&msg));
+        }
+
+        if method == AUTH_NONE {
+            return Ok(());
+        }
+
+        if username.len() > 255 || password.len() > 255 {
+            return Err(Error::convert_string("socks5 proxy username or password is too long !"));
+        }
+
+        // RFC 1929
+        let mut request = vec![0x01, username.len() as u8];
+        request.extend_from_slice(username.as_bytes());
+        request.push(password.len() as u8);
+        request.extend_from_slice(password.as_bytes());
+        tcp.write_all(&request).map_err(|err| Self::error("send socks5 auth", err))?;
+
+        let mut response = [0u8; 2];
+        tcp.read_exact(&mut response).map_err(|err| Self::error("read socks5 auth", err))?;
+        if response[1] != 0x00 {
+            let msg = "socks5 proxy auth `username` and `password` failed !";
+            error!("{}", msg);
+            return Err(Error::convert_string(msg));
+        }
+
+        Ok(())
+    }
+
+    /// 请求代理连接目标地址
+    fn request_connect(tcp: &mut TcpStream, host: &str, port: u16) -> Result<(), String> {
+        let mut request = vec![SOCKS_VERSION, CMD_CONNECT, 0x00];
+        match host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => {
+                request.push(ATYP_IPV4);
+                request.extend_from_slice(&ip.octets());
+            }
+            Ok(IpAddr::V6(ip)) => {
+                request.push(ATYP_IPV6);
+                request.extend_from_slice(&ip.octets());
+            }
+            Err(_) => {
+                if host.len() > 255 {
+                    return Err(Error::convert_string(&format!("socks5 target host `{}` is too long !", host)));
+                }
+
+                request.push(ATYP_DOMAIN);
+                request.push(host.len() as u8);
+                request.extend_from_slice(host.as_bytes());
+            }
+        }
+        request.extend_from_slice(&port.to_be_bytes());
+        tcp.write_all(&request).map_err(|err| Self::error("send socks5 connect", err))?;
+
+        let mut response = [0u8; 4];
+        tcp.read_exact(&mut response).map_err(|err| Self::error("read socks5 connect", err))?;
+        if response[1] != 0x00 {
+            let msg = format!("socks5 proxy connect to {}:{} failed, reply code: {}", host, port, response[1]);
+            error!("{}", &msg);
+            return Err(Error::convert_string(&msg));
+        }
+
+        // 读取并丢弃绑定地址
+        let address_len = match response[3] {
+            ATYP_IPV4 => 4,
+            ATYP_IPV6 => 16,
+            ATYP_DOMAIN => {
+                let mut len = [0u8; 1];
+                tcp.read_exact(&mut len).map_err(|err| Self::error("read socks5 bind address", err))?;
+                len[0] as usize
+            }
+            atyp => return Err(Error::convert_string(&format!("socks5 proxy reply unknown address type: {}", atyp))),
+        };
+
+        let mut bind_address = vec![0u8; address_len + 2];
+        tcp.read_exact(&mut bind_address).map_err(|err| Self::error("read socks5 bind address", err))?;
+        Ok(())
+    }
+
+    fn error(action: &str, err: std::io::Error) -> String {
+        let msg = format!("{} error: {:#?}", action, err);
+        error!("{}", &msg);
+        Error::convert_string(&msg)
+    }
+}
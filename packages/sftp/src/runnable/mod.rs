@@ -1,19 +1,24 @@
 //! 远程文件对比，并运行读取日志
 
-use crate::config::{Server, ValidateCopy};
+use crate::config::{FileUploadOptions, PsConfig, RemotePerm, Server, SupervisorConfig, ValidateCopy};
 use crate::sftp::SftpHandler;
 use handlers::error::Error;
 use log::{error, info};
-use ssh2::{Session, Sftp};
+use ssh2::{Channel, Session, Sftp};
 use std::ffi::OsStr;
-use std::io::Read;
+use std::io::{ErrorKind, Read};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 pub struct SftpRunnableHandler;
 
+const DEFAULT_KILL_TIMEOUT: Duration = Duration::from_secs(10); // 等待进程退出的默认时间
+const KILL_POLL_INTERVAL: Duration = Duration::from_millis(500); // 检查进程是否退出的间隔
+const DEFAULT_RESTART_DELAY: Duration = Duration::from_secs(1); // 程序退出后重启的默认等待时间, 每次重启翻倍
+
 impl SftpRunnableHandler {
     pub fn exec<F>(server: Server, copy: ValidateCopy, log_func: F) -> Result<String, String>
     where
@@ -87,7 +92,7 @@ impl SftpRunnableHandler {
         SftpHandler::log_info(&format!("server dest file dir: {:#?}", dest_dir), log_func.clone());
 
         // 判断目录是否存在
-        SftpHandler::check_dir(&sftp, &dest_dir.as_path().to_string_lossy().to_string(), log_func.clone())?;
+        SftpHandler::check_dir(&sftp, &dest_dir.as_path().to_string_lossy().to_string(), RemotePerm::default().dir, log_func.clone())?;
 
         // 获取服务器文件的 hash 值
         let dest_file_path = dest_dir.join(&file_name).as_path().to_string_lossy().to_string();
@@ -109,22 +114,22 @@ impl SftpRunnableHandler {
         };
 
         // 如果程序存存在, 则判断是否已启动
-        let mut pid = String::new();
+        let mut pids: Vec<String> = Vec::new();
         if sftp.stat(Path::new(&dest_file_path)).is_ok() {
-            pid = Self::judge_program_running(&session, &file_name, log_func.clone())?;
+            pids = Self::judge_program_running(&session, &dest_file_path, log_func.clone())?;
         }
 
-        SftpHandler::log_info(&format!("program pid: {}", pid), log_func.clone());
+        SftpHandler::log_info(&format!("program pids: {:?}", pids), log_func.clone());
         // 上传文件
         if !is_hash_equal {
             SftpHandler::log_info(&format!("begin to upload file: {}", &file_name), log_func.clone());
 
             // 如果程序启动则结束进行
-            if !pid.is_empty() {
-                Self::kill_pid(&session, &pid)?;
+            if !pids.is_empty() {
+                Self::kill_pid(&session, &pids, &dest_file_path, &PsConfig::default(), None, log_func.clone())?;
             }
 
-            SftpHandler::upload(&sftp, &copy.file_path, &dest_dir.as_path().to_string_lossy().to_string(), &file_name, log_func.clone())?;
+            SftpHandler::upload(&sftp, &copy.file_path, &dest_dir.as_path().to_string_lossy().to_string(), &file_name, &FileUploadOptions::default(), None, log_func.clone())?;
             SftpHandler::log_info(&format!("upload file `{}` success", &file_name), log_func.clone());
         } else {
             SftpHandler::log_info("compare program no different !", log_func.clone());
@@ -136,6 +141,16 @@ impl SftpRunnableHandler {
 
     /// 运行程序, 如果程序已被杀死, 或者没有被杀死且 pid 为空, 则 启动程序
     pub fn exec_program<F, D>(sess: Option<Session>, server: &Server, dest_file_path: &str, secs: Option<u64>, func: F, log_func: D) -> Result<(), String>
+    where
+        F: FnMut(&str),
+        D: FnMut(&str),
+    {
+        Self::exec_program_supervised(sess, server, dest_file_path, secs, SupervisorConfig::default(), func, log_func)
+    }
+
+    /// 运行程序并监控, 程序以非 0 退出码退出时自动重启, 见 `SupervisorConfig`
+    /// 设置了 restart_on_exit 且重启次数用完时返回错误
+    pub fn exec_program_supervised<F, D>(sess: Option<Session>, server: &Server, dest_file_path: &str, secs: Option<u64>, supervisor: SupervisorConfig, func: F, log_func: D) -> Result<(), String>
     where
         F: FnMut(&str),
         D: FnMut(&str),
@@ -159,44 +174,151 @@ impl SftpRunnableHandler {
         })?;
 
         // 判断程序是否在运行
-        let mut pid = String::new();
-        let file_name = Path::new(&dest_file_path).file_name().unwrap_or(OsStr::new("")).to_string_lossy().to_string();
+        let mut pids: Vec<String> = Vec::new();
         if sftp.stat(Path::new(&dest_file_path)).is_ok() {
-            pid = SftpRunnableHandler::judge_program_running(&session, &file_name, log_func.clone())?;
+            pids = SftpRunnableHandler::judge_program_running(&session, dest_file_path, log_func.clone())?;
         }
 
         // 如果在运行，则直接结束
-        if !pid.is_empty() {
-            SftpRunnableHandler::kill_pid(&session, &pid)?;
+        if !pids.is_empty() {
+            SftpRunnableHandler::kill_pid(&session, &pids, dest_file_path, &PsConfig::default(), None, log_func.clone())?;
         }
 
         let time = if let Some(secs) = secs { secs } else { 1 };
 
-        info!("start program {} ...", dest_file_path);
         let func = Arc::new(Mutex::new(func));
-        let mut channel = SftpHandler::create_channel(&session)?;
+        let SupervisorConfig { restart_on_exit, restart_delay, stop } = supervisor;
+        let max_restarts = restart_on_exit.unwrap_or(0);
+        let mut restart_delay = restart_delay.unwrap_or(DEFAULT_RESTART_DELAY);
+        let mut restarts = 0;
+        loop {
+            info!("start program {} ...", dest_file_path);
+            let mut channel = SftpHandler::create_channel(&session)?;
+
+            // 通道一直会开着的, 因为要监听程序的输出, 当通道关闭后, 程序也结束
+            channel.exec(dest_file_path).map_err(|err| {
+                let msg = format!("start program `{}` error: {:#?}", dest_file_path, err);
+                error!("{}", &msg);
+                SftpHandler::close_channel_in_err(&mut channel);
+                Error::convert_string(&msg)
+            })?;
+
+            Self::read_channel_output(&session, &mut channel, Some(Duration::from_secs(time)), stop.as_deref(), func.clone());
+
+            // 调用方结束监控
+            if Self::is_stopped(stop.as_deref()) {
+                SftpHandler::log_info(&format!("stop supervising program {} !", dest_file_path), log_func.clone());
+                SftpHandler::close_channel_in_err(&mut channel);
+                return Ok(());
+            }
+
+            // 没有设置重启, 保持原来的行为
+            if restart_on_exit.is_none() {
+                return Ok(());
+            }
+
+            let exit_status = SftpHandler::get_channel_exit_status(&mut channel)?;
+            if exit_status == 0 {
+                SftpHandler::log_info(&format!("program {} exited normally !", dest_file_path), log_func.clone());
+                return Ok(());
+            }
+
+            if restarts >= max_restarts {
+                let msg = format!("program `{}` exited with code {}, restarted {} times, give up !", dest_file_path, exit_status, restarts);
+                SftpHandler::log_error(&msg, log_func.clone());
+                return Err(Error::convert_string(&msg));
+            }
+
+            restarts += 1;
+            SftpHandler::log_info(
+                &format!("program {} exited with code {}, restart ({}/{}) after {:?} ...", dest_file_path, exit_status, restarts, max_restarts, restart_delay),
+                log_func.clone(),
+            );
+            if !Self::sleep_until_stopped(restart_delay, stop.as_deref()) {
+                SftpHandler::log_info(&format!("stop supervising program {} !", dest_file_path), log_func.clone());
+                return Ok(());
+            }
+
+            restart_delay *= 2;
+        }
+    }
+
+    /// 是否已结束
+    fn is_stopped(stop: Option<&AtomicBool>) -> bool {
+        return stop.map(|stop| stop.load(Ordering::SeqCst)).unwrap_or(false);
+    }
+
+    /// 等待一段时间, 期间 stop 为 true 时提前返回 false
+    fn sleep_until_stopped(delay: Duration, stop: Option<&AtomicBool>) -> bool {
+        let start_time = Instant::now();
+        while start_time.elapsed() < delay {
+            if Self::is_stopped(stop) {
+                return false;
+            }
 
-        // 通道一直会开着的, 因为要监听程序的输出, 当通道关闭后, 程序也结束
-        channel.exec(dest_file_path).map_err(|err| {
-            let msg = format!("start program `{}` error: {:#?}", dest_file_path, err);
+            thread::sleep(KILL_POLL_INTERVAL.min(delay.saturating_sub(start_time.elapsed())));
+        }
+
+        return !Self::is_stopped(stop);
+    }
+
+    /// 读取远程日志文件, follow 为 true 时使用 `tail -f` 持续输出, lines 为读取的最后行数, 默认为 10
+    /// stop 设置为 true 时结束读取
+    pub fn tail_file<F>(session: &Session, file_path: &str, follow: bool, lines: Option<u32>, stop: Option<Arc<AtomicBool>>, func: F) -> Result<(), String>
+    where
+        F: FnMut(&str),
+    {
+        if file_path.is_empty() {
+            let msg = "tail file failed, file path is empty !";
+            info!("{}", msg);
+            return Err(Error::convert_string(&msg));
+        }
+
+        let cmd = format!("tail {}-n {} '{}'", if follow { "-f " } else { "" }, lines.unwrap_or(10), file_path.replace('\'', "'\\''"));
+        info!("tail file: {}", cmd);
+
+        let func = Arc::new(Mutex::new(func));
+        let mut channel = SftpHandler::create_channel(session)?;
+        channel.exec(&cmd).map_err(|err| {
+            let msg = format!("tail file `{}` error: {:#?}", file_path, err);
             error!("{}", &msg);
             SftpHandler::close_channel_in_err(&mut channel);
             Error::convert_string(&msg)
         })?;
 
+        Self::read_channel_output(session, &mut channel, None, stop.as_deref(), func);
+
+        // 结束远程 tail 进程
+        SftpHandler::close_channel_in_err(&mut channel);
+        Ok(())
+    }
+
+    /// 读取通道输出并执行函数, 直到通道关闭或 stop 为 true
+    /// 有 stop 时使用非阻塞读取, 以便及时结束
+    fn read_channel_output<F>(session: &Session, channel: &mut Channel, interval: Option<Duration>, stop: Option<&AtomicBool>, func: Arc<Mutex<F>>)
+    where
+        F: FnMut(&str),
+    {
+        if stop.is_some() {
+            session.set_blocking(false);
+        }
+
         let mut stdout = channel.stream(0); // 0表示标准输出
         let mut buffer = [0; 4096];
         loop {
-            let bytes = match stdout.read(&mut buffer) {
-                Ok(bytes) => Some(bytes),
-                Err(_) => None,
-            };
-
-            if bytes.is_none() {
+            if Self::is_stopped(stop) {
                 break;
             }
 
-            let bytes = bytes.unwrap();
+            let bytes = match stdout.read(&mut buffer) {
+                Ok(bytes) => bytes,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(100));
+                    continue;
+                }
+                Err(_) => break,
+            };
+
             if bytes == 0 {
                 break;
             }
@@ -208,24 +330,39 @@ impl SftpRunnableHandler {
             // 执行函数
             let mut exec_func = func.lock().unwrap();
             (*exec_func)(&output.to_string());
-            thread::sleep(Duration::from_secs(time));
+            drop(exec_func);
+
+            if let Some(interval) = interval {
+                thread::sleep(interval);
+            }
         }
 
-        Ok(())
+        if stop.is_some() {
+            session.set_blocking(true);
+        }
     }
 
-    /// 判断程序是否已启动 `ps aux | grep xxx | grep -v grep`
-    pub fn judge_program_running<F>(session: &Session, file_name: &str, log_func: Arc<Mutex<F>>) -> Result<String, String>
+    /// 判断程序是否已启动, 使用默认的 `ps aux`, 返回所有匹配的 pid
+    /// program_path 为远程程序的完整路径, 只匹配可执行文件路径完全一致的进程
+    pub fn judge_program_running<F>(session: &Session, program_path: &str, log_func: Arc<Mutex<F>>) -> Result<Vec<String>, String>
+    where
+        F: FnMut(&str),
+    {
+        Self::judge_program_running_by_ps(session, program_path, &PsConfig::default(), log_func)
+    }
+
+    /// 判断程序是否已启动, 读取 ps 输出后根据表头解析 pid, 返回所有匹配的 pid
+    pub fn judge_program_running_by_ps<F>(session: &Session, program_path: &str, ps: &PsConfig, log_func: Arc<Mutex<F>>) -> Result<Vec<String>, String>
     where
         F: FnMut(&str),
     {
         SftpHandler::log_info("judge program running", log_func.clone());
         let mut channel = SftpHandler::create_channel(&session)?;
 
-        let cmd = format!("ps aux | grep {} | grep -v grep", file_name);
+        let cmd = if ps.command.trim().is_empty() { PsConfig::default().command } else { ps.command.trim().to_string() };
         SftpHandler::log_info(&format!("judge program running command: {}", cmd), log_func.clone());
         channel.exec(&cmd).map_err(|err| {
-            let msg = format!("grep process `{}` error: {:#?}", file_name, err);
+            let msg = format!("grep process `{}` error: {:#?}", program_path, err);
             error!("{}", &msg);
             SftpHandler::close_channel_in_err(&mut channel);
             Error::convert_string(&msg)
@@ -239,23 +376,114 @@ impl SftpRunnableHandler {
             Error::convert_string(&msg)
         })?;
 
-        SftpHandler::log_info(&format!("judge program running output: {}", output), log_func.clone());
-        let pid: Option<&str> = output.lines().filter(|line| line.contains(file_name) && !line.contains("grep")).next().and_then(|line| line.split_whitespace().nth(1));
         SftpHandler::close_channel(&mut channel)?;
-        if let Some(pid) = pid {
-            return Ok(pid.to_string());
+        let pids = Self::parse_ps_pids(&output, program_path, ps.pid_column);
+        SftpHandler::log_info(&format!("judge program running pids: {:?}", pids), log_func.clone());
+        return Ok(pids);
+    }
+
+    /// 解析 ps 输出, 获取第一个匹配 program_path 的进程 pid
+    pub fn parse_ps_pid(output: &str, program_path: &str, pid_column: Option<usize>) -> Option<String> {
+        return Self::parse_ps_pids(output, program_path, pid_column).into_iter().next();
+    }
+
+    /// 解析 ps 输出, 获取所有匹配 program_path 的进程 pid
+    /// 可执行文件路径需要和 program_path 完全一致, program_path 不包含 `/` 时按文件名匹配
+    /// pid_column 为空时, 根据表头 `PID` 所在列查找, 兼容 Linux、macOS 和 BusyBox
+    pub fn parse_ps_pids(output: &str, program_path: &str, pid_column: Option<usize>) -> Vec<String> {
+        let mut pids: Vec<String> = Vec::new();
+        let program_path = program_path.trim();
+        if program_path.is_empty() {
+            return pids;
+        }
+
+        let is_match = |executable: &str| {
+            if program_path.contains('/') {
+                executable == program_path
+            } else {
+                Path::new(executable).file_name().map(|name| name == program_path).unwrap_or(false)
+            }
+        };
+
+        let mut column = pid_column;
+        let mut command_column: Option<usize> = None;
+        for line in output.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.is_empty() {
+                continue;
+            }
+
+            // 表头
+            if let Some(index) = fields.iter().position(|field| *field == "PID") {
+                if column.is_none() {
+                    column = Some(index);
+                }
+                command_column = fields.iter().position(|field| matches!(*field, "COMMAND" | "CMD" | "ARGS"));
+                continue;
+            }
+
+            // 有表头时只比较命令列, 否则比较所有列
+            let matched = match command_column {
+                Some(index) => fields.get(index).map(|executable| is_match(executable)).unwrap_or(false),
+                None => fields.iter().any(|field| is_match(field)),
+            };
+
+            if !matched {
+                continue;
+            }
+
+            let pid = fields.get(column.unwrap_or(1)).unwrap_or(&"");
+            if !pid.is_empty() && pid.chars().all(|c| c.is_ascii_digit()) {
+                pids.push(pid.to_string());
+            }
+        }
+
+        return pids;
+    }
+
+    /// 杀掉进程, 先发送 SIGTERM, 等待进程退出, 超时(默认 10 秒)后仍在运行则发送 SIGKILL
+    /// program_path 和 ps 用于检查进程是否退出, 返回是否使用了 SIGKILL
+    pub fn kill_pid<F>(session: &Session, pids: &[String], program_path: &str, ps: &PsConfig, timeout: Option<Duration>, log_func: Arc<Mutex<F>>) -> Result<bool, String>
+    where
+        F: FnMut(&str),
+    {
+        if pids.is_empty() {
+            return Ok(false);
+        }
+
+        SftpHandler::log_info(&format!("kill process pids `{:?}` ...", pids), log_func.clone());
+        Self::send_signal(session, pids, "kill")?;
+
+        // 等待进程退出
+        let timeout = timeout.unwrap_or(DEFAULT_KILL_TIMEOUT);
+        let start_time = Instant::now();
+        let mut running_pids: Vec<String>;
+        loop {
+            running_pids = Self::judge_program_running_by_ps(session, program_path, ps, log_func.clone())?.into_iter().filter(|pid| pids.contains(pid)).collect();
+            if running_pids.is_empty() {
+                SftpHandler::log_info(&format!("process pids `{:?}` exited after {:?}", pids, start_time.elapsed()), log_func.clone());
+                return Ok(false);
+            }
+
+            if start_time.elapsed() >= timeout {
+                break;
+            }
+
+            thread::sleep(KILL_POLL_INTERVAL);
         }
 
-        return Ok(String::new());
+        SftpHandler::log_info(&format!("process pids `{:?}` are still running after {:?}, force kill them ...", running_pids, timeout), log_func.clone());
+        Self::send_signal(session, &running_pids, "kill -9")?;
+        Ok(true)
     }
 
-    /// 杀掉进程
-    pub fn kill_pid(session: &Session, pid: &str) -> Result<(), String> {
+    /// 向进程发送信号
+    fn send_signal(session: &Session, pids: &[String], kill: &str) -> Result<(), String> {
         let mut channel = SftpHandler::create_channel(session)?;
 
-        let cmd = format!("kill {}", pid);
+        let cmd = format!("{} {}", kill, pids.join(" "));
         channel.exec(&cmd).map_err(|err| {
-            let msg = format!("kill process pid `{}` error: {:#?}", pid, err);
+            let msg = format!("kill process pids `{:?}` error: {:#?}", pids, err);
             error!("{}", &msg);
             SftpHandler::close_channel_in_err(&mut channel);
             Error::convert_string(&msg)
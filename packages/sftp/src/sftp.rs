@@ -1,22 +1,32 @@
 //! sftp
 
-use crate::config::Server;
+use crate::config::{FileUploadOptions, Server};
+use crate::jump::JumpHost;
+use crate::proxy::Socks5;
 use crypto_hash::{hex_digest, Algorithm};
 use handlers::error::Error;
 use handlers::file::FileHandler;
 use indicatif::{ProgressBar, ProgressStyle};
 use log::{error, info};
-use ssh2::{Channel, FileStat, Session, Sftp};
-use std::io::{Read, Write};
+use ssh2::{Channel, CheckResult, ErrorCode, FileStat, HashType, KnownHostFileKind, MethodType, Session, Sftp};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{ErrorKind, Read, Write};
 use std::net::{SocketAddr, TcpStream};
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub struct SftpHandler;
 
 const DEFAULT_TIMEOUT: u64 = 10;
+const DEFAULT_RETRY_DELAY: u64 = 3; // 默认重试间隔(秒)
+const LIBSSH2_ERROR_TIMEOUT: i32 = -9;
+const LIBSSH2_ERROR_FILE: i32 = -16; // 读取私钥失败, 通常为密码错误
+const UPLOAD_CHUNK_SIZE: usize = 64 * 1024; // 上传时每次读取的字节数
 
 impl SftpHandler {
     /// 连接服务器
@@ -24,22 +34,48 @@ impl SftpHandler {
     where
         F: FnMut(&str),
     {
-        let address = format!("{}:{}", &server.host, server.port);
-        let socket = SocketAddr::from_str(&address).map_err(|err| {
-            let msg = format!("convert {} to socket address error: {:#?}", &address, err);
-            error!("{}", &msg);
-            Error::convert_string(&msg)
-        })?;
+        let retries = server.retries.unwrap_or(1).max(1);
+        let retry_delay = Duration::from_secs(server.retry_delay_secs.unwrap_or(DEFAULT_RETRY_DELAY));
+
+        let mut attempt = 1;
+        loop {
+            if retries > 1 {
+                Self::log_info(&format!("connect attempt {}/{} ...", attempt, retries), log_func.clone());
+            }
 
+            match Self::connect_once(server, log_func.clone()) {
+                Ok(session) => return Ok(session),
+                Err(err) => {
+                    if attempt >= retries {
+                        if retries == 1 {
+                            return Err(err);
+                        }
+
+                        let msg = format!("connect failed after {} attempts, error: {}", retries, err);
+                        error!("{}", &msg);
+                        return Err(Error::convert_string(&msg));
+                    }
+
+                    Self::log_error(&format!("connect attempt {}/{} failed, retry after {:#?}: {}", attempt, retries, retry_delay, err), log_func.clone());
+                    thread::sleep(retry_delay);
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// 连接一次: tcp 连接、握手、认证
+    fn connect_once<F>(server: &Server, log_func: Arc<Mutex<F>>) -> Result<Session, String>
+    where
+        F: FnMut(&str),
+    {
+        let address = format!("{}:{}", &server.host, server.port);
         let timeout = Self::get_time_out(server.timeout);
-        Self::log_info(&format!("connect timeout: {:#?}", timeout), log_func.clone());
+        let auth_timeout = server.auth_timeout.filter(|auth_timeout| *auth_timeout > 0).map(Duration::from_secs).unwrap_or(timeout);
+        Self::log_info(&format!("connect timeout: {:#?}, auth timeout: {:#?}", timeout, auth_timeout), log_func.clone());
 
         Self::log_info("create tcp ..", log_func.clone());
-        let tcp = TcpStream::connect_timeout(&socket, timeout).map_err(|err| {
-            let msg = format!("connect to {} error: {:#?}", &address, err);
-            error!("{}", &msg);
-            Error::convert_string(&msg)
-        })?;
+        let tcp = Self::create_tcp(server, &address, timeout, log_func.clone())?;
 
         Self::log_info("create session ..", log_func.clone());
         let mut session = Session::new().map_err(|err| {
@@ -49,20 +85,28 @@ impl SftpHandler {
         })?;
 
         session.set_tcp_stream(tcp);
+        Self::set_session_options(&mut session, server, log_func.clone())?;
+
+        // 握手和认证超时, 防止服务器不响应时一直阻塞
+        session.set_timeout(auth_timeout.as_millis() as u32);
 
         Self::log_info("session handshake ..", log_func.clone());
         session.handshake().map_err(|err| {
-            let msg = format!("connect to {} error: {:#?}", &address, err);
+            let msg = if Self::is_timeout(&err) {
+                format!("handshake with {} timeout after {:#?}", &address, auth_timeout)
+            } else {
+                format!("connect to {} error: {:#?}", &address, err)
+            };
             error!("{}", &msg);
             Error::convert_string(&msg)
         })?;
 
+        if server.host_key_check {
+            Self::check_host_key(&session, server, log_func.clone())?;
+        }
+
         Self::log_info("session auth ..", log_func.clone());
-        session.userauth_password(&server.username, &server.password).map_err(|err| {
-            let msg = format!("auth {} `user` and `password` error: {:#?}", &address, err);
-            error!("{}", &msg);
-            Error::convert_string(&msg)
-        })?;
+        Self::auth(&session, server, &address, auth_timeout)?;
 
         if !session.authenticated() {
             let msg = format!("authentication server: {} failed !", &address);
@@ -70,12 +114,177 @@ impl SftpHandler {
             return Err(Error::convert_string(&msg));
         }
 
+        // 操作超时, 0 表示不限制
+        let operation_timeout = server.operation_timeout.map(|operation_timeout| Duration::from_secs(operation_timeout).as_millis() as u32).unwrap_or(0);
+        session.set_timeout(operation_timeout);
+
         Self::log_info(&format!("connect {} success !", &address), log_func.clone());
         Ok(session)
     }
 
-    /// 文件上传
-    pub(crate) fn upload<F>(sftp: &Sftp, file_path: &str, dest_dir: &str, file_name: &str, log_func: Arc<Mutex<F>>) -> Result<(), String>
+    /// 使用 `~/.ssh/known_hosts` 校验服务器公钥, 防止中间人攻击
+    fn check_host_key<F>(session: &Session, server: &Server, log_func: Arc<Mutex<F>>) -> Result<(), String>
+    where
+        F: FnMut(&str),
+    {
+        let address = format!("{}:{}", &server.host, server.port);
+        let check_error = |msg: String| {
+            Self::log_error(&msg, log_func.clone());
+            Error::convert_string(&msg)
+        };
+
+        let home_dir = std::env::var("HOME")
+            .or_else(|_| std::env::var("USERPROFILE"))
+            .map_err(|_| check_error(format!("check host key of {} failed, can not get user home dir !", &address)))?;
+        let known_hosts_path = Path::new(&home_dir).join(".ssh").join("known_hosts");
+        Self::log_info(&format!("check host key of {} by {:?} ..", &address, known_hosts_path), log_func.clone());
+
+        let mut known_hosts = session.known_hosts().map_err(|err| check_error(format!("check host key of {} error: {:#?}", &address, err)))?;
+        known_hosts
+            .read_file(&known_hosts_path, KnownHostFileKind::OpenSSH)
+            .map_err(|err| check_error(format!("check host key of {} failed, read {:?} error: {:#?}", &address, known_hosts_path, err)))?;
+
+        let (key, key_type) = session.host_key().ok_or_else(|| check_error(format!("check host key of {} failed, can not get host key !", &address)))?;
+        let fingerprint = session
+            .host_key_hash(HashType::Sha256)
+            .map(|hash| hash.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<String>>().join(":"))
+            .unwrap_or(String::new());
+
+        let port = u16::try_from(server.port).unwrap_or(22);
+        match known_hosts.check_port(&server.host, port, key) {
+            CheckResult::Match => {
+                Self::log_info(&format!("check host key of {} success !", &address), log_func.clone());
+                Ok(())
+            }
+            CheckResult::NotFound => Err(check_error(format!(
+                "check host key of {} failed, host is unknown, please add it to {:?}, key type: {:?}, SHA256 fingerprint: {}",
+                &address, known_hosts_path, key_type, fingerprint
+            ))),
+            CheckResult::Mismatch => Err(check_error(format!(
+                "check host key of {} failed, host key mismatch, it may be a man-in-the-middle attack, key type: {:?}, SHA256 fingerprint: {}",
+                &address, key_type, fingerprint
+            ))),
+            CheckResult::Failure => Err(check_error(format!("check host key of {} failed !", &address))),
+        }
+    }
+
+    /// 认证, 设置了私钥时使用公钥认证, 否则使用密码认证
+    fn auth(session: &Session, server: &Server, address: &str, auth_timeout: Duration) -> Result<(), String> {
+        let result = match server.get_private_key() {
+            Some(private_key) => {
+                let private_key_path = Path::new(private_key);
+                if !private_key_path.exists() {
+                    let msg = format!("auth {} failed, private key `{}` not exists !", address, private_key);
+                    error!("{}", &msg);
+                    return Err(Error::convert_string(&msg));
+                }
+
+                let public_key = server.public_key.as_deref().map(|public_key| public_key.trim()).filter(|public_key| !public_key.is_empty());
+                if let Some(public_key) = public_key {
+                    if !Path::new(public_key).exists() {
+                        let msg = format!("auth {} failed, public key `{}` not exists !", address, public_key);
+                        error!("{}", &msg);
+                        return Err(Error::convert_string(&msg));
+                    }
+                }
+
+                session.userauth_pubkey_file(&server.username, public_key.map(Path::new), private_key_path, server.passphrase.as_deref()).map_err(|err| {
+                    if Self::is_timeout(&err) {
+                        format!("auth {} timeout after {:#?}", address, auth_timeout)
+                    } else if err.code() == ErrorCode::Session(LIBSSH2_ERROR_FILE) {
+                        format!("auth {} failed, can not read private key `{}`, please check the `passphrase`: {:#?}", address, private_key, err)
+                    } else {
+                        format!("auth {} `user` and `private key` error: {:#?}", address, err)
+                    }
+                })
+            }
+            None => session.userauth_password(&server.username, &server.password).map_err(|err| {
+                if Self::is_timeout(&err) {
+                    format!("auth {} timeout after {:#?}", address, auth_timeout)
+                } else {
+                    format!("auth {} `user` and `password` error: {:#?}", address, err)
+                }
+            }),
+        };
+
+        return result.map_err(|msg| {
+            error!("{}", &msg);
+            Error::convert_string(&msg)
+        });
+    }
+
+    /// 设置 session 选项(压缩、算法偏好), 需要在握手之前
+    fn set_session_options<F>(session: &mut Session, server: &Server, log_func: Arc<Mutex<F>>) -> Result<(), String>
+    where
+        F: FnMut(&str),
+    {
+        if let Some(compress) = server.compress {
+            Self::log_info(&format!("session compress: {}", compress), log_func.clone());
+            session.set_compress(compress);
+        }
+
+        let mut prefs: Vec<(MethodType, &str, &String)> = Vec::new();
+        if let Some(ciphers) = server.ciphers.as_ref().filter(|ciphers| !ciphers.trim().is_empty()) {
+            prefs.push((MethodType::CryptCs, "ciphers", ciphers));
+            prefs.push((MethodType::CryptSc, "ciphers", ciphers));
+        }
+
+        if let Some(kex) = server.kex.as_ref().filter(|kex| !kex.trim().is_empty()) {
+            prefs.push((MethodType::Kex, "kex", kex));
+        }
+
+        for (method_type, name, pref) in prefs {
+            session.method_pref(method_type, pref.trim()).map_err(|err| {
+                let msg = format!("set session {} `{}` error: {:#?}", name, pref, err);
+                error!("{}", &msg);
+                Error::convert_string(&msg)
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// 创建 tcp 连接, 配置了代理时通过 socks5 代理连接
+    fn create_tcp<F>(server: &Server, address: &str, timeout: Duration, log_func: Arc<Mutex<F>>) -> Result<TcpStream, String>
+    where
+        F: FnMut(&str),
+    {
+        if let Some(jump) = &server.jump {
+            let port = u16::try_from(server.port).map_err(|_| Error::convert_string(&format!("invalid server port: {}", server.port)))?;
+            return JumpHost::connect(jump, &server.host, port, log_func.clone());
+        }
+
+        if let Some(proxy) = &server.proxy {
+            Self::log_info(&format!("connect {} by socks5 proxy {}:{} ..", address, &proxy.host, proxy.port), log_func.clone());
+            let port = u16::try_from(server.port).map_err(|_| Error::convert_string(&format!("invalid server port: {}", server.port)))?;
+            return Socks5::connect(proxy, &server.host, port, timeout);
+        }
+
+        let socket = SocketAddr::from_str(address).map_err(|err| {
+            let msg = format!("convert {} to socket address error: {:#?}", address, err);
+            error!("{}", &msg);
+            Error::convert_string(&msg)
+        })?;
+
+        TcpStream::connect_timeout(&socket, timeout).map_err(|err| {
+            let msg = if err.kind() == ErrorKind::TimedOut {
+                format!("connect to {} timeout after {:#?}", address, timeout)
+            } else {
+                format!("connect to {} error: {:#?}", address, err)
+            };
+            error!("{}", &msg);
+            Error::convert_string(&msg)
+        })
+    }
+
+    /// 判断是否是 ssh 超时错误
+    fn is_timeout(err: &ssh2::Error) -> bool {
+        err.code() == ErrorCode::Session(LIBSSH2_ERROR_TIMEOUT)
+    }
+
+    /// 上传文件, 按块读取本地文件, 每写入一块调用 on_progress(已上传字节数, 文件总字节数)
+    /// options: 远程文件权限, 以及是否保留本地文件的访问和修改时间
+    pub fn upload<F>(sftp: &Sftp, file_path: &str, dest_dir: &str, file_name: &str, options: &FileUploadOptions, mut on_progress: Option<&mut dyn FnMut(u64, u64)>, log_func: Arc<Mutex<F>>) -> Result<(), String>
     where
         F: FnMut(&str),
     {
@@ -86,7 +295,7 @@ impl SftpHandler {
         }
 
         // 判断目录是否存在, 不存在则创建
-        Self::check_dir(sftp, dest_dir, log_func.clone())?;
+        Self::check_dir(sftp, dest_dir, options.perm.dir, log_func.clone())?;
         let remote_file_path = Path::new(dest_dir).join(file_name);
         let remote_file_path_str = remote_file_path.as_path().to_string_lossy().to_string();
 
@@ -107,18 +316,47 @@ impl SftpHandler {
 
         Self::log_info(&format!("uploading file {} ...", file_path), log_func.clone());
 
+        let read_error = |err: std::io::Error| {
+            let msg = format!("upload file `{}` failed, read file error: {:#?}", file_path, err);
+            error!("{}", &msg);
+            Error::convert_string(&msg)
+        };
+
+        let mut local_file = fs::File::open(file_path).map_err(read_error)?;
+        let file_size = local_file.metadata().map_err(read_error)?.len();
+
         // progress bar
-        let pb = ProgressBar::new_spinner();
-        // pb.enable_steady_tick(Duration::from_millis(120));
-        pb.set_style(ProgressStyle::with_template("{spinner:.blue} {msg}").unwrap().tick_strings(&["▹▹▹▹▹", "▸▹▹▹▹", "▹▸▹▹▹", "▹▹▸▹▹", "▹▹▹▸▹", "▹▹▹▹▸", "▪▪▪▪▪"]));
+        let pb = ProgressBar::new(file_size);
+        pb.set_style(
+            ProgressStyle::with_template("{spinner:.blue} {msg} [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec})")
+                .unwrap()
+                .tick_strings(&["▹▹▹▹▹", "▸▹▹▹▹", "▹▸▹▹▹", "▹▹▸▹▹", "▹▹▹▸▹", "▹▹▹▹▸", "▪▪▪▪▪"])
+                .progress_chars("=> "),
+        );
         pb.set_message(format!("Uploading {}...", file_path));
 
-        let buffer = FileHandler::read_file_buffer(file_path)?;
-        remote_file.write_all(&buffer).map_err(|err| {
-            let msg = format!("upload file `{}` error: {:#?}", file_path, err);
-            error!("{}", &msg);
-            Error::convert_string(&msg)
-        })?;
+        // 按块上传, 内存占用与文件大小无关
+        let mut buffer = vec![0u8; UPLOAD_CHUNK_SIZE];
+        let mut uploaded: u64 = 0;
+        loop {
+            let size = local_file.read(&mut buffer).map_err(read_error)?;
+            if size == 0 {
+                break;
+            }
+
+            remote_file.write_all(&buffer[..size]).map_err(|err| {
+                let msg = format!("upload file `{}` error: {:#?}", file_path, err);
+                error!("{}", &msg);
+                pb.abandon();
+                Error::convert_string(&msg)
+            })?;
+
+            uploaded += size as u64;
+            pb.set_position(uploaded);
+            if let Some(on_progress) = on_progress.as_mut() {
+                on_progress(uploaded, file_size);
+            }
+        }
 
         pb.finish_with_message(format!("Upload File {} Success !", file_path));
 
@@ -127,15 +365,16 @@ impl SftpHandler {
 
         // 设置文件权限
         Self::log_info(&format!("begin to set file `{}` permission ...", file_path), log_func.clone());
+        let (atime, mtime) = if options.preserve_mtime { Self::get_file_times(Path::new(file_path)) } else { (None, None) };
         sftp.setstat(
             &remote_file_path,
             FileStat {
                 size: None,
                 uid: None,
                 gid: None,
-                perm: Some(0o777),
-                atime: None,
-                mtime: None,
+                perm: Some(options.perm.file as u32),
+                atime,
+                mtime,
             },
         )
         .map_err(|err| {
@@ -148,8 +387,97 @@ impl SftpHandler {
         Ok(())
     }
 
+    /// 获取本地文件的访问和修改时间(秒)
+    pub(crate) fn get_file_times(file_path: &Path) -> (Option<u64>, Option<u64>) {
+        let metadata = match fs::metadata(file_path) {
+            Ok(metadata) => metadata,
+            Err(_) => return (None, None),
+        };
+
+        let to_secs = |time: std::io::Result<SystemTime>| time.ok().and_then(|time| time.duration_since(UNIX_EPOCH).ok()).map(|duration| duration.as_secs());
+        let mtime = to_secs(metadata.modified());
+        let atime = to_secs(metadata.accessed()).or(mtime);
+        return (atime, mtime);
+    }
+
+    /// 批量上传文件到 dest_dir, 每个文件上传结束后调用 on_file_complete(文件名, 是否成功)
+    pub fn upload_files<F, C>(sftp: &Sftp, file_paths: &[String], dest_dir: &str, options: &FileUploadOptions, on_file_complete: Option<C>, log_func: Arc<Mutex<F>>) -> Result<u64, String>
+    where
+        F: FnMut(&str),
+        C: FnMut(&str, bool),
+    {
+        let files: Vec<(String, String)> = file_paths
+            .iter()
+            .map(|file_path| {
+                let file_name = Path::new(file_path).file_name().unwrap_or(OsStr::new("")).to_string_lossy().to_string();
+                (file_path.clone(), file_name)
+            })
+            .collect();
+
+        Self::upload_file_list(sftp, files, dest_dir, options, on_file_complete, log_func)
+    }
+
+    /// 递归上传目录, 每个文件上传结束后调用 on_file_complete(相对路径, 是否成功)
+    pub fn upload_dir<F, C>(sftp: &Sftp, local_dir: &str, dest_dir: &str, options: &FileUploadOptions, on_file_complete: Option<C>, log_func: Arc<Mutex<F>>) -> Result<u64, String>
+    where
+        F: FnMut(&str),
+        C: FnMut(&str, bool),
+    {
+        let local_dir_path = Path::new(local_dir);
+        if !local_dir_path.is_dir() {
+            let msg = format!("upload dir failed, dir path: {} is not a dir !", local_dir);
+            error!("{}", &msg);
+            return Err(Error::convert_string(&msg));
+        }
+
+        let file_paths = FileHandler::read_dir_files(local_dir_path)?;
+        let files: Vec<(String, String)> = file_paths
+            .into_iter()
+            .map(|file_path| {
+                let relative_path = Path::new(&file_path).strip_prefix(local_dir_path).unwrap_or(Path::new(&file_path)).to_string_lossy().to_string();
+                (file_path, relative_path)
+            })
+            .collect();
+
+        Self::upload_file_list(sftp, files, dest_dir, options, on_file_complete, log_func)
+    }
+
+    /// 逐个上传文件, files 为 (本地路径, 相对路径), 返回上传成功的文件个数
+    fn upload_file_list<F, C>(sftp: &Sftp, files: Vec<(String, String)>, dest_dir: &str, options: &FileUploadOptions, mut on_file_complete: Option<C>, log_func: Arc<Mutex<F>>) -> Result<u64, String>
+    where
+        F: FnMut(&str),
+        C: FnMut(&str, bool),
+    {
+        let mut success_count: u64 = 0;
+        let mut failed_files: Vec<String> = Vec::new();
+        for (file_path, relative_path) in files.iter() {
+            let remote_file_path = Path::new(dest_dir).join(relative_path);
+            let remote_dir = remote_file_path.parent().unwrap_or(Path::new(dest_dir)).to_string_lossy().to_string();
+            let file_name = remote_file_path.file_name().unwrap_or(OsStr::new("")).to_string_lossy().to_string();
+
+            let success = Self::upload(sftp, file_path, &remote_dir, &file_name, options, None, log_func.clone()).is_ok();
+            if success {
+                success_count += 1;
+            } else {
+                failed_files.push(relative_path.clone());
+            }
+
+            if let Some(on_file_complete) = on_file_complete.as_mut() {
+                on_file_complete(relative_path, success);
+            }
+        }
+
+        if !failed_files.is_empty() {
+            let msg = format!("upload files failed, failed files: {:#?}", failed_files);
+            Self::log_error(&msg, log_func.clone());
+            return Err(Error::convert_string(&msg));
+        }
+
+        Ok(success_count)
+    }
+
     /// 判断目录是否存在, 不存在则创建
-    pub(crate) fn check_dir<F>(sftp: &Sftp, file_path: &str, log_func: Arc<Mutex<F>>) -> Result<(), String>
+    pub(crate) fn check_dir<F>(sftp: &Sftp, file_path: &str, dir_perm: i32, log_func: Arc<Mutex<F>>) -> Result<(), String>
     where
         F: FnMut(&str),
     {
@@ -162,11 +490,35 @@ impl SftpHandler {
 
         // 不存在则创建
         Self::log_info(&format!("remote file dir: `{}` is not exists, it will be created!", file_path), log_func.clone());
-        sftp.mkdir(&path, 0o777).map_err(|err| {
-            let msg = format!("mkdir file path `{}` error: {:#?}", file_path, err);
-            error!("{}", &msg);
-            Error::convert_string(&msg)
-        })?;
+        Self::mkdir_p(sftp, file_path, dir_perm, log_func.clone())
+    }
+
+    /// 递归创建目录, 依次创建不存在的上级目录, mode 如 0o755
+    pub fn mkdir_p<F>(sftp: &Sftp, dir_path: &str, mode: i32, log_func: Arc<Mutex<F>>) -> Result<(), String>
+    where
+        F: FnMut(&str),
+    {
+        let mut current = PathBuf::new();
+        for component in Path::new(dir_path).components() {
+            current.push(component);
+
+            // 根目录或已存在的目录直接跳过
+            if matches!(component, Component::RootDir | Component::Prefix(_)) || sftp.stat(&current).is_ok() {
+                continue;
+            }
+
+            Self::log_info(&format!("mkdir remote dir: `{}`", current.display()), log_func.clone());
+            if let Err(err) = sftp.mkdir(&current, mode) {
+                // 可能被其他进程创建
+                if sftp.stat(&current).is_ok() {
+                    continue;
+                }
+
+                let msg = format!("mkdir file path `{}` error: {:#?}", current.display(), err);
+                error!("{}", &msg);
+                return Err(Error::convert_string(&msg));
+            }
+        }
 
         Ok(())
     }
@@ -206,12 +558,83 @@ impl SftpHandler {
         Ok(hex_digest(Algorithm::SHA256, &buffer))
     }
 
-    /// 获取用户主目录
+    /// 通过一次 `sha256sum` 命令获取远程目录下所有文件的 hash, 返回 路径 -> hash
+    /// 远程没有 `sha256sum` 时返回错误, 调用方可回退到逐个文件获取
+    pub fn get_remote_hashes(session: &Session, dir: &str) -> Result<HashMap<String, String>, String> {
+        let mut channel = Self::create_channel(session)?;
+        channel.exec(&format!("find '{}' -type f -exec sha256sum {{}} +", dir.replace('\'', "'\\''"))).map_err(|err| {
+            let msg = format!("get remote hashes in `{}` error: {:#?}", dir, err);
+            error!("{}", &msg);
+            Self::close_channel_in_err(&mut channel);
+            Error::convert_string(&msg)
+        })?;
+
+        let (content, error) = Self::get_channel_output(&mut channel)?;
+        Self::close_channel(&mut channel)?;
+        let exit_status = Self::get_channel_exit_status(&mut channel)?;
+        if exit_status != 0 {
+            let msg = format!("get remote hashes in `{}` failed, exit code: {}, error: {}", dir, exit_status, error.trim());
+            error!("{}", &msg);
+            return Err(Error::convert_string(&msg));
+        }
+
+        let mut hashes: HashMap<String, String> = HashMap::new();
+        for line in content.lines() {
+            // 格式: `<hash>  <path>`, 二进制模式为 `<hash> *<path>`, 带转义的文件名以 `\` 开头, 跳过
+            if line.starts_with('\\') || line.len() < 67 || !line.is_char_boundary(64) || !line.is_char_boundary(66) {
+                continue;
+            }
+
+            let (hash, file_path) = line.split_at(64);
+            let file_path = &file_path[2..];
+            hashes.insert(Self::normalize_path(file_path), hash.to_string());
+        }
+
+        Ok(hashes)
+    }
+
+    /// 统一路径格式, 如去掉多余的 `/`, 用于 hash 查找
+    pub(crate) fn normalize_path(file_path: &str) -> String {
+        return Path::new(file_path).components().collect::<PathBuf>().to_string_lossy().to_string();
+    }
+
+    /// 获取用户主目录, 优先使用 `getent`, 失败时由 shell 展开
     pub(crate) fn get_user_home_dir(session: &Session, username: &str) -> Result<String, String> {
+        // 1. getent, 精简容器和部分 BSD 系统没有
+        match Self::read_command_output(session, &format!("getent passwd {}", username)) {
+            Ok(output) => {
+                let fields: Vec<&str> = output.trim().split(':').collect();
+                if fields.len() >= 6 && !fields[5].trim().is_empty() {
+                    return Ok(fields[5].trim().to_string());
+                }
+
+                info!("get user `{}` home dir by getent failed, output: {}", username, output.trim());
+            }
+            Err(err) => info!("get user `{}` home dir by getent error: {}", username, err),
+        }
+
+        // 2. 由 shell 展开 `~username`, 未展开时使用当前登录用户的 $HOME
+        for cmd in [format!("eval echo ~{}", username), String::from("echo $HOME")] {
+            match Self::read_command_output(session, &cmd) {
+                Ok(output) => {
+                    let home_dir = output.trim();
+                    if !home_dir.is_empty() && !home_dir.starts_with('~') {
+                        return Ok(home_dir.to_string());
+                    }
+                }
+                Err(err) => info!("get user `{}` home dir by `{}` error: {}", username, cmd, err),
+            }
+        }
+
+        return Err(Error::convert_string(&format!("get user `{}` home dir failed !", username)));
+    }
+
+    /// 执行命令并读取标准输出
+    fn read_command_output(session: &Session, cmd: &str) -> Result<String, String> {
         let mut channel = Self::create_channel(&session)?;
 
-        channel.exec(&format!("getent passwd {}", username)).map_err(|err| {
-            let msg = format!("get user `{}` home dir error: {:#?}", username, err);
+        channel.exec(cmd).map_err(|err| {
+            let msg = format!("exec command `{}` error: {:#?}", cmd, err);
             error!("{}", &msg);
             Self::close_channel_in_err(&mut channel);
             Error::convert_string(&msg)
@@ -219,20 +642,14 @@ impl SftpHandler {
 
         let mut output = String::new();
         channel.read_to_string(&mut output).map_err(|err| {
-            let msg = format!("read grep command error: {:#?}", err);
+            let msg = format!("read command `{}` output error: {:#?}", cmd, err);
             error!("{}", &msg);
             Self::close_channel_in_err(&mut channel);
             Error::convert_string(&msg)
         })?;
 
         Self::close_channel(&mut channel)?;
-
-        let fields: Vec<&str> = output.trim().split(':').collect();
-        if fields.len() >= 6 {
-            return Ok(fields[5].to_string());
-        }
-
-        return Err(Error::convert_string(&format!("get user `{}` home dir failed !", username)));
+        Ok(output)
     }
 
     /// 创建 channel
@@ -301,7 +718,22 @@ impl SftpHandler {
         Ok(())
     }
 
-    /// 关闭 session
+    /// 获取命令退出码, 需要在 channel 关闭后调用
+    pub fn get_channel_exit_status(channel: &mut Channel) -> Result<i32, String> {
+        channel.wait_close().map_err(|err| {
+            let msg = format!("wait channel close error: {:#?}", err);
+            error!("{}", &msg);
+            Error::convert_string(&msg)
+        })?;
+
+        channel.exit_status().map_err(|err| {
+            let msg = format!("get command exit status error: {:#?}", err);
+            error!("{}", &msg);
+            Error::convert_string(&msg)
+        })
+    }
+
+    /// 关闭 session, 通过跳板机连接时, 目标连接关闭后转发线程会同时关闭跳板机 session
     pub fn close_session(session: Session) -> Result<(), String> {
         session.disconnect(None, "Bye bye !", None).map_err(|err| {
             let msg = format!("close session error: {:#?}", err);
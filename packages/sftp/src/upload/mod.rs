@@ -1,20 +1,25 @@
 //! 文件上传, 压缩, 比较等
 
-use crate::config::{Server, SftpUploadResult, Upload};
+use crate::config::{CompressionFormat, FileUploadOptions, LocalDirDiff, Server, SftpUploadResult, Upload};
 use crate::sftp::SftpHandler;
+use glob::{MatchOptions, Pattern};
 use handlers::error::Error;
 use handlers::file::FileHandler;
 use handlers::utils::Utils;
 use log::{error, info};
 use rayon::prelude::*;
 use regex::Regex;
-use ssh2::{Session, Sftp};
+use ssh2::{FileStat, Session, Sftp};
+use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
-const UPLOAD_TEMP_DIR: &str = "__SFTP_TEMP_DIR__"; // 临时上传目录
+const UPLOAD_TEMP_DIR: &str = "__SFTP_TEMP_DIR__"; // 临时上传目录, 每次上传在其中创建单独的子目录
+const DEFAULT_UNZIP_RETRIES: u32 = 3; // 远程解压默认尝试次数
+const UNZIP_RETRY_DELAY: Duration = Duration::from_secs(1); // 远程解压重试间隔
 pub struct SftpUpload;
 
 #[derive(Debug, Default, Clone)]
@@ -24,15 +29,111 @@ struct SftpUploadDifferent {
     relative_path: String, // 文件的相对路径
 }
 
+/// 本地压缩包
+struct UploadPackage {
+    zip_file_path: String,                                   // 压缩包全路径
+    file_name: String,                                       // 上传文件名
+    file_times: HashMap<String, (Option<u64>, Option<u64>)>, // 压缩前本地文件的访问和修改时间
+}
+
+/// 文件 hash 获取方式
+struct FileHashSource<'a> {
+    sftp: Option<&'a Sftp>,              // 为空时, 两个目录都在本地
+    hashes: &'a HashMap<String, String>, // 通过 `SftpHandler::get_remote_hashes` 批量获取的远程文件 hash, 找不到时逐个获取
+    is_local: bool,                      // 新文件是否为本地文件
+}
+
+/// 文件过滤, 根据 `excludes` 和 `includes` 判断文件是否需要上传
+#[derive(Debug, Default, Clone)]
+struct FileFilter {
+    excludes: Vec<Pattern>,
+    includes: Vec<Pattern>,
+}
+
+impl FileFilter {
+    const OPTIONS: MatchOptions = MatchOptions {
+        case_sensitive: false,
+        require_literal_separator: false,
+        require_literal_leading_dot: false,
+    };
+
+    fn new(upload: &Upload) -> Result<Self, String> {
+        let compile = |patterns: &Vec<String>| -> Result<Vec<Pattern>, String> {
+            patterns
+                .iter()
+                .filter(|pattern| !pattern.trim().is_empty())
+                .map(|pattern| {
+                    Pattern::new(pattern.trim()).map_err(|err| {
+                        let msg = format!("upload failed, invalid pattern `{}`: {}", pattern, err);
+                        error!("{}", &msg);
+                        Error::convert_string(&msg)
+                    })
+                })
+                .collect()
+        };
+
+        Ok(Self {
+            excludes: compile(&upload.excludes)?,
+            includes: compile(&upload.includes)?,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        return self.excludes.is_empty() && self.includes.is_empty();
+    }
+
+    /// 判断文件或目录是否保留, relative_paths 为需要匹配的相对路径, 任意一个被排除即排除
+    /// includes 只对文件生效, 目录由其中的文件决定
+    fn is_match(&self, relative_paths: &[&Path], is_dir: bool) -> bool {
+        let matches = |patterns: &Vec<Pattern>| relative_paths.iter().any(|path| patterns.iter().any(|pattern| pattern.matches_path_with(path, Self::OPTIONS)));
+        if matches(&self.excludes) {
+            return false;
+        }
+
+        return is_dir || self.includes.is_empty() || matches(&self.includes);
+    }
+
+    /// 判断路径是否保留, 使用相对 dirs 的路径匹配
+    fn is_path_match(&self, path: &Path, dirs: &[&Path], is_dir: bool) -> bool {
+        let relative_paths: Vec<&Path> = dirs.iter().filter_map(|dir| path.strip_prefix(dir).ok()).collect();
+        return self.is_match(&relative_paths, is_dir);
+    }
+
+    /// 过滤文件列表, 使用相对 dirs 的路径匹配
+    fn filter_files(&self, files: Vec<String>, dirs: &[&Path]) -> Vec<String> {
+        if self.is_empty() {
+            return files;
+        }
+
+        return files.into_iter().filter(|file| self.is_path_match(Path::new(file), dirs, false)).collect();
+    }
+}
+
 impl SftpUpload {
     pub fn exec<F>(server: Server, upload: Upload, log_func: F) -> Result<SftpUploadResult, String>
+    where
+        F: FnMut(&str),
+    {
+        Self::exec_upload(None, &server, upload, log_func)
+    }
+
+    /// 使用已连接的 session 上传, session 由调用方负责关闭, `server` 只用于记录 host
+    pub fn exec_with_session<F>(session: &Session, server: &Server, upload: Upload, log_func: F) -> Result<SftpUploadResult, String>
+    where
+        F: FnMut(&str),
+    {
+        Self::exec_upload(Some(session), server, upload, log_func)
+    }
+
+    /// 上传, 如果 sess 为空, 则连接服务器
+    fn exec_upload<F>(sess: Option<&Session>, server: &Server, upload: Upload, log_func: F) -> Result<SftpUploadResult, String>
     where
         F: FnMut(&str),
     {
         let log_func = Arc::new(Mutex::new(log_func));
         SftpHandler::log_info(&format!("exec upload args: {:#?}", &upload), log_func.clone());
 
-        if server.is_empty() {
+        if sess.is_none() && server.is_empty() {
             let msg = "exec upload failed, one of `host`、`port`、`username` and `password` server items is empty !";
             info!("{}", msg);
             return Err(Error::convert_string(&msg));
@@ -51,6 +152,8 @@ impl SftpUpload {
             return Err(Error::convert_string(&msg));
         }
 
+        let filter = FileFilter::new(&upload)?;
+
         // 读取目录
         let (directories, files) = FileHandler::read_dir(&upload_dir_path)?;
         if directories.is_empty() && files.is_empty() {
@@ -73,27 +176,249 @@ impl SftpUpload {
         // 文件名路径
         let file_path = PathBuf::from(&upload.dir).join(&file_name);
 
+        // 各阶段耗时
+        let mut timings: HashMap<String, Duration> = HashMap::new();
+
+        // 压缩前记录本地文件时间, 压缩后原目录会被删除
+        let file_times = if upload.preserve_mtime {
+            Self::get_local_file_times(&upload, &filter, &file_path, &directories, &files)?
+        } else {
+            HashMap::new()
+        };
+
         // 压缩目录
         SftpHandler::log_info("compress upload dir ...", log_func.clone());
-        let zip_file_path = Self::compress_upload_dir(&upload, &file_path, directories.clone(), files.clone())?;
+        let start_time = Instant::now();
+        let zip_file_path = Self::compress_upload_dir(&upload, &filter, &file_path, directories.clone(), files.clone())?;
 
         SftpHandler::log_info("rename file upload path ...", log_func.clone());
-        let zip_file_path = Self::rename_file_upload_path(&zip_file_path)?; // 临时文件目录
+        let zip_file_path = Self::rename_file_upload_path(&zip_file_path, upload.compression)?; // 临时文件目录
+        timings.insert(String::from("compress"), start_time.elapsed());
 
         // 连接服务器
-        SftpHandler::log_info("create session ...", log_func.clone());
-        let session = SftpHandler::connect(&server, log_func.clone())?;
+        let start_time = Instant::now();
+        let connected_session;
+        let session = match sess {
+            Some(sess) => sess,
+            None => {
+                SftpHandler::log_info("create session ...", log_func.clone());
+                connected_session = SftpHandler::connect(&server, log_func.clone())?;
+                &connected_session
+            }
+        };
+
         let sftp = session.sftp().map_err(|err| {
             let msg = format!("exec upload error: {:#?}", err);
             error!("{}", &msg);
             Error::convert_string(&msg)
         })?;
+        timings.insert(String::from("connect"), start_time.elapsed());
 
         // 文件上传和发布
-        let result = Self::upload_and_publish(&session, &sftp, &server, &upload, &zip_file_path, &file_name, log_func.clone())?;
+        let package = UploadPackage { zip_file_path, file_name, file_times };
+        let mut result = Self::upload_and_publish(session, &sftp, &server, &upload, &package, &mut timings, log_func.clone())?;
+        SftpHandler::log_info(&format!("upload timings: {:#?}", timings), log_func.clone());
+        result.timings = timings;
         Ok(result)
     }
 
+    /// 只执行服务器命令, 不上传文件, 如: `nginx -s reload`, 返回命令输出
+    pub fn run_remote_cmds<F>(server: Server, cmds: Vec<String>, log_func: F) -> Result<String, String>
+    where
+        F: FnMut(&str),
+    {
+        let log_func = Arc::new(Mutex::new(log_func));
+
+        if server.is_empty() {
+            let msg = "run remote commands failed, one of `host`、`port`、`username` and `password` server items is empty !";
+            info!("{}", msg);
+            return Err(Error::convert_string(&msg));
+        }
+
+        let cmds: Vec<String> = cmds.iter().map(|cmd| cmd.trim().to_string()).filter(|cmd| !cmd.is_empty()).collect();
+        if cmds.is_empty() {
+            let msg = "run remote commands failed, `cmds` is empty !";
+            info!("{}", msg);
+            return Err(Error::convert_string(&msg));
+        }
+
+        // 连接服务器
+        let session = SftpHandler::connect(&server, log_func.clone())?;
+        let output = match Self::exec_command(&session, cmds, log_func.clone()) {
+            Ok(output) => output,
+            Err(err) => {
+                let _ = SftpHandler::close_session(session);
+                return Err(err);
+            }
+        };
+
+        SftpHandler::close_session(session)?;
+        Ok(output)
+    }
+
+    /// 只比较本地目录与服务器目录的差异, 不上传任何文件
+    pub fn diff_only<F>(server: Server, upload: Upload, log_func: F) -> Result<SftpUploadResult, String>
+    where
+        F: FnMut(&str),
+    {
+        let log_func = Arc::new(Mutex::new(log_func));
+        SftpHandler::log_info(&format!("exec diff only args: {:#?}", &upload), log_func.clone());
+
+        if server.is_empty() {
+            let msg = "exec diff only failed, one of `host`、`port`、`username` and `password` server items is empty !";
+            info!("{}", msg);
+            return Err(Error::convert_string(&msg));
+        }
+
+        if upload.is_empty() {
+            let msg = "exec diff only failed, one of `dir` and `server_dir` upload items is empty !";
+            info!("{}", msg);
+            return Err(Error::convert_string(&msg));
+        }
+
+        let upload_dir_path = PathBuf::from(&upload.dir);
+        if !upload_dir_path.exists() {
+            let msg = format!("exec diff only failed, upload dir: {} is not exists !", &upload.dir);
+            info!("{}", msg);
+            return Err(Error::convert_string(&msg));
+        }
+
+        // 读取目录
+        let (directories, files) = FileHandler::read_dir(&upload_dir_path)?;
+        if directories.is_empty() && files.is_empty() {
+            let msg = format!("exec diff only failed, upload dir: {} is empty !", &upload.dir);
+            info!("{}", msg);
+            return Err(Error::convert_string(&msg));
+        }
+
+        // 获取上传文件名
+        let file_name = Self::get_upload_file_name(&upload, directories.clone(), files.clone())?;
+        if file_name.is_empty() {
+            let msg = "exec diff only failed, can not get filename !";
+            info!("{}", msg);
+            return Err(Error::convert_string(&msg));
+        }
+
+        // 本地比较目录, 同 `compress_upload_dir` 的打包规则
+        let file_path = PathBuf::from(&upload.dir).join(&file_name);
+        let local_dir = if directories.len() == 1 && files.is_empty() && file_path.is_dir() { file_path } else { upload_dir_path };
+        let local_dir_str = local_dir.to_string_lossy().to_string();
+
+        let local_files = FileHandler::read_dir_files(&local_dir)?;
+        let filter = FileFilter::new(&upload)?;
+        let local_files = filter.filter_files(local_files, &[&local_dir, Path::new(&upload.dir)]);
+        if local_files.is_empty() {
+            let msg = format!("exec diff only failed, local dir: {} has no files !", &local_dir_str);
+            info!("{}", msg);
+            return Err(Error::convert_string(&msg));
+        }
+
+        // 连接服务器
+        SftpHandler::log_info("create session ...", log_func.clone());
+        let session = SftpHandler::connect(&server, log_func.clone())?;
+        let sftp = session.sftp().map_err(|err| {
+            let msg = format!("exec diff only error: {:#?}", err);
+            error!("{}", &msg);
+            Error::convert_string(&msg)
+        })?;
+
+        let file_name_stem = Path::new(&file_name).file_stem().unwrap_or(OsStr::new("")).to_string_lossy().to_string();
+        let server_file_dir = Path::new(&upload.server_dir).join(&file_name_stem).to_string_lossy().to_string();
+        SftpHandler::log_info(&format!("diff local dir: {} with server dir: {}", &local_dir_str, &server_file_dir), log_func.clone());
+
+        let mut result = SftpUploadResult::default();
+        result.host = server.host.clone();
+        result.need_increment = true;
+
+        // 读取服务器目录文件列表
+        let mut server_files: Vec<String> = Vec::new();
+        if sftp.stat(Path::new(&server_file_dir)).is_ok() {
+            Self::read_files(&sftp, &server_file_dir, &mut server_files);
+        }
+        let server_files = filter.filter_files(server_files, &[Path::new(&server_file_dir)]);
+
+        let hashes = if server_files.is_empty() {
+            HashMap::new()
+        } else {
+            Self::get_remote_hashes(&session, &[&server_file_dir], log_func.clone())
+        };
+        let differences = Self::get_compare_file(
+            &FileHashSource {
+                sftp: Some(&sftp),
+                hashes: &hashes,
+                is_local: true,
+            },
+            &server_files,
+            &local_files,
+            &server_file_dir,
+            &local_dir_str,
+            log_func.clone(),
+        );
+        result.file_count = differences.len() as u64;
+        result.file_list = differences.iter().map(|d| d.relative_path.clone()).collect();
+        result.changed_files = result.file_list.clone();
+
+        let (remove_cmds, deleted_files) = Self::remove_no_used_files_in_dir(&server_files, &local_files, &server_file_dir, &local_dir_str, log_func.clone());
+        result.delete_file_count = remove_cmds.len() as u64;
+        result.deleted_files = deleted_files;
+
+        SftpHandler::log_info(&format!("diff only finished, difference file count: {}, delete file count: {}", result.file_count, result.delete_file_count), log_func.clone());
+        SftpHandler::close_session(session)?;
+        Ok(result)
+    }
+
+    /// 离线比较两个本地目录, 如上次发布的快照和新的构建目录, 返回新增、修改和删除的文件(相对路径)
+    pub fn diff_local_dirs<F>(old_dir: &str, new_dir: &str, log_func: F) -> Result<LocalDirDiff, String>
+    where
+        F: FnMut(&str),
+    {
+        let log_func = Arc::new(Mutex::new(log_func));
+        for dir in [old_dir, new_dir] {
+            if !Path::new(dir).is_dir() {
+                let msg = format!("diff local dirs failed, dir: {} is not exists !", dir);
+                info!("{}", msg);
+                return Err(Error::convert_string(&msg));
+            }
+        }
+
+        let old_files = FileHandler::read_dir_files(old_dir)?;
+        let new_files = FileHandler::read_dir_files(new_dir)?;
+
+        SftpHandler::log_info(&format!("diff local dir: {} with dir: {}", new_dir, old_dir), log_func.clone());
+        let differences = Self::get_compare_file(
+            &FileHashSource {
+                sftp: None,
+                hashes: &HashMap::new(),
+                is_local: true,
+            },
+            &old_files,
+            &new_files,
+            old_dir,
+            new_dir,
+            log_func.clone(),
+        );
+
+        let mut diff = LocalDirDiff::default();
+        for difference in differences.iter() {
+            if difference.old_path.is_empty() {
+                diff.added.push(difference.relative_path.clone());
+            } else {
+                diff.changed.push(difference.relative_path.clone());
+            }
+        }
+
+        diff.removed = Self::get_no_used_files(&old_files, &new_files, old_dir, new_dir)
+            .iter()
+            .map(|file| Path::new(file).strip_prefix(old_dir).unwrap_or(Path::new(file)).to_string_lossy().to_string())
+            .collect();
+
+        diff.added.sort();
+        diff.changed.sort();
+        diff.removed.sort();
+        SftpHandler::log_info(&format!("diff local dirs finished, added: {}, changed: {}, removed: {}", diff.added.len(), diff.changed.len(), diff.removed.len()), log_func.clone());
+        Ok(diff)
+    }
+
     /// 获取上传文件名
     fn get_upload_file_name(upload: &Upload, directories: Vec<String>, files: Vec<String>) -> Result<String, String> {
         if let Some(server_file_name) = &upload.server_file_name {
@@ -124,13 +449,13 @@ impl SftpUpload {
     }
 
     /// 压缩文件
-    fn compress_upload_dir(upload: &Upload, file_path: &PathBuf, directories: Vec<String>, files: Vec<String>) -> Result<String, String> {
+    fn compress_upload_dir(upload: &Upload, filter: &FileFilter, file_path: &PathBuf, directories: Vec<String>, files: Vec<String>) -> Result<String, String> {
         // 文件名路径
         let file_path_str = file_path.to_string_lossy().to_string();
 
         // zip 包路径
         let mut zip_file_path = file_path.clone();
-        zip_file_path.set_extension("zip");
+        zip_file_path.set_extension(upload.compression.extension());
         let zip_file_path_str = zip_file_path.to_string_lossy().to_string();
         // 1. 判断目录下有没有待上传文件.zip 包
         if directories.len() == 0 && files.len() == 1 && zip_file_path.exists() {
@@ -139,7 +464,7 @@ impl SftpUpload {
 
         // 2. 当目录下存在且只有一个 `文件名` 的目录
         if directories.len() == 1 && files.len() == 0 && file_path.exists() {
-            return Self::generate_zip(&file_path_str, &zip_file_path_str);
+            return Self::generate_zip(upload, filter, &file_path_str, &zip_file_path_str);
         }
 
         // 3. 其他情况: 创建目录, 移动目录到新目录, 然后压缩
@@ -179,12 +504,18 @@ impl SftpUpload {
         FileHandler::rename(&file_random_path_str, &file_path_str)?;
 
         // 压缩目录
-        return Self::generate_zip(&file_path_str, &zip_file_path_str);
+        return Self::generate_zip(upload, filter, &file_path_str, &zip_file_path_str);
     }
 
-    /// 生成 zip
-    fn generate_zip(file_path: &str, zip_file_path: &str) -> Result<String, String> {
-        let success = Utils::generate_zip(file_path, zip_file_path)?;
+    /// 生成 zip 或 tar.gz, 排除的文件不会被压缩
+    fn generate_zip(upload: &Upload, filter: &FileFilter, file_path: &str, zip_file_path: &str) -> Result<String, String> {
+        let dirs = [Path::new(file_path), Path::new(&upload.dir)];
+        let is_match = |path: &Path| filter.is_empty() || filter.is_path_match(path, &dirs, path.is_dir());
+        let success = match upload.compression {
+            CompressionFormat::TarGz => Utils::generate_tar_gz_with_filter(file_path, zip_file_path, is_match)?,
+            CompressionFormat::Zip if upload.need_parallel_zip.unwrap_or(false) => Utils::generate_zip_parallel_with_filter(file_path, zip_file_path, is_match)?,
+            CompressionFormat::Zip => Utils::generate_zip_with_filter(file_path, zip_file_path, is_match)?,
+        };
         if !success {
             let msg = format!("upload failed, generate zip: {:#?} failed !", zip_file_path);
             error!("{}", msg);
@@ -196,34 +527,78 @@ impl SftpUpload {
         return Ok(zip_file_path.to_string());
     }
 
+    /// 获取本地文件的访问和修改时间, key 为解压目录下的相对路径, 同 `compress_upload_dir` 的打包规则
+    fn get_local_file_times(upload: &Upload, filter: &FileFilter, file_path: &PathBuf, directories: &Vec<String>, files: &Vec<String>) -> Result<HashMap<String, (Option<u64>, Option<u64>)>, String> {
+        let mut file_times = HashMap::new();
+
+        // 已经是 zip 包, 使用 zip 包中的时间
+        if directories.is_empty() && files.len() == 1 && file_path.with_extension(upload.compression.extension()).exists() {
+            return Ok(file_times);
+        }
+
+        let local_dir = if directories.len() == 1 && files.is_empty() && file_path.is_dir() { file_path.clone() } else { PathBuf::from(&upload.dir) };
+        let local_files = FileHandler::read_dir_files(&local_dir)?;
+        let local_files = filter.filter_files(local_files, &[&local_dir, Path::new(&upload.dir)]);
+        for local_file in local_files.iter() {
+            let local_file_path = Path::new(local_file);
+            let relative_path = local_file_path.strip_prefix(&local_dir).unwrap_or(local_file_path).to_string_lossy().to_string();
+            file_times.insert(relative_path, SftpHandler::get_file_times(local_file_path));
+        }
+
+        return Ok(file_times);
+    }
+
+    /// 设置解压后文件的访问和修改时间
+    fn set_remote_file_times<F>(sftp: &Sftp, unzip_dir_str: &str, file_times: &HashMap<String, (Option<u64>, Option<u64>)>, log_func: Arc<Mutex<F>>)
+    where
+        F: FnMut(&str),
+    {
+        SftpHandler::log_info(&format!("set file times in unzip dir: {}, file count: {} ...", unzip_dir_str, file_times.len()), log_func.clone());
+        for (relative_path, (atime, mtime)) in file_times.iter() {
+            let remote_file_path = Path::new(unzip_dir_str).join(relative_path);
+            let stat = FileStat {
+                size: None,
+                uid: None,
+                gid: None,
+                perm: None,
+                atime: *atime,
+                mtime: *mtime,
+            };
+
+            if let Err(err) = sftp.setstat(&remote_file_path, stat) {
+                SftpHandler::log_error(&format!("set file `{:?}` times error: {:#?}", remote_file_path, err), log_func.clone());
+            }
+        }
+    }
+
     /// 文件上传
-    fn upload_and_publish<F>(session: &Session, sftp: &Sftp, server: &Server, upload: &Upload, zip_file_path: &str, file_name: &str, log_func: Arc<Mutex<F>>) -> Result<SftpUploadResult, String>
+    fn upload_and_publish<F>(session: &Session, sftp: &Sftp, server: &Server, upload: &Upload, package: &UploadPackage, timings: &mut HashMap<String, Duration>, log_func: Arc<Mutex<F>>) -> Result<SftpUploadResult, String>
     where
         F: FnMut(&str),
     {
+        let UploadPackage { zip_file_path, file_name, file_times } = package;
         SftpHandler::log_info("upload and publish ...", log_func.clone());
 
         // 获取远程临时存放目录
-        let server_dir = &upload.server_dir.trim();
-        let mut server_temp_path = PathBuf::from(server_dir);
-        if let Some(parent) = server_temp_path.parent() {
-            server_temp_path = parent.join(&UPLOAD_TEMP_DIR)
-        } else {
-            server_temp_path = server_temp_path.join(&UPLOAD_TEMP_DIR);
-        }
-
-        let server_temp_path_str = server_temp_path.to_string_lossy().to_string(); // 远程临时存放目录
+        let server_temp_path_str = Self::get_server_temp_dir(upload); // 远程临时存放目录
+        SftpHandler::log_info(&format!("server temp dir: {}", &server_temp_path_str), log_func.clone());
 
         // 判断目录是否存在
         SftpHandler::log_info("check dir ...", log_func.clone());
-        SftpHandler::check_dir(&sftp, &server_temp_path_str, log_func.clone())?;
+        SftpHandler::check_dir(&sftp, &server_temp_path_str, upload.get_remote_perm().dir, log_func.clone())?;
 
         let zip_file_name = Path::new(zip_file_path).file_name().unwrap_or(OsStr::new("")).to_string_lossy().to_string();
 
         // 1. 上传
         SftpHandler::log_info(&format!("begin to uploading file {} and set file permission ...", zip_file_path), log_func.clone());
 
-        SftpHandler::upload(sftp, zip_file_path, &server_temp_path_str, &zip_file_name, log_func.clone())?;
+        let start_time = Instant::now();
+        let options = FileUploadOptions {
+            perm: upload.get_remote_perm(),
+            preserve_mtime: upload.preserve_mtime,
+        };
+        SftpHandler::upload(sftp, zip_file_path, &server_temp_path_str, &zip_file_name, &options, None, log_func.clone())?;
+        timings.insert(String::from("upload"), start_time.elapsed());
 
         SftpHandler::log_info(&format!("uploading file {} and set file permission success !", zip_file_path), log_func.clone());
 
@@ -233,30 +608,53 @@ impl SftpUpload {
         let unzip_dir_str = unzip_dir.to_string_lossy().to_string();
 
         info!("server unzip dir: {}", &unzip_dir_str);
-        let server_file_path = PathBuf::from(&server_temp_path_str).join(&zip_file_name);
-        match Self::uncompress_zip(session, sftp, &server_temp_path_str, &zip_file_name, &unzip_dir_str, log_func.clone()) {
+        let start_time = Instant::now();
+        match Self::uncompress_zip_with_retry(session, sftp, upload, &server_temp_path_str, &zip_file_name, &unzip_dir_str, log_func.clone()) {
             Ok(_) => {}
             Err(err) => {
-                let msg = format!("uncompress zip: {:?} error: {:#?} !", server_file_path, err);
+                let msg = format!("uncompress zip: {} error: {:#?} !", zip_file_name, err);
                 error!("{}", msg);
-                Self::end(sftp, session, &server_file_path, &unzip_dir_str, zip_file_path, true, log_func.clone());
+                Self::end(session, &server_temp_path_str, zip_file_path, true, upload.keep_temp, log_func.clone());
                 return Err(Error::convert_string(&msg));
             }
         };
+        timings.insert(String::from("unzip"), start_time.elapsed());
+
+        // 保留本地文件时间, zip 包中为固定时间
+        if upload.preserve_mtime && !file_times.is_empty() {
+            Self::set_remote_file_times(sftp, &unzip_dir_str, file_times, log_func.clone());
+        }
+
+        // 设置解压后的文件和目录权限, zip 包中默认为 0o777
+        if upload.has_perm() {
+            let perm = upload.get_remote_perm();
+            let commands = vec![
+                format!("find {} -type d -exec chmod {:o} {{}} +", &unzip_dir_str, perm.dir),
+                format!("find {} -type f -exec chmod {:o} {{}} +", &unzip_dir_str, perm.file),
+            ];
+            if let Err(err) = Self::exec_command(session, commands, log_func.clone()) {
+                let msg = format!("set unzip dir: {} permission error: {}", &unzip_dir_str, err);
+                error!("{}", msg);
+                Self::end(session, &server_temp_path_str, zip_file_path, true, upload.keep_temp, log_func.clone());
+                return Err(Error::convert_string(&msg));
+            }
+        }
 
         // 3. 比较发布
         let server_file_dir = Path::new(&upload.server_dir).join(&file_name_stem);
+        let start_time = Instant::now();
 
         // 获取发布命令
-        let result = match Self::touch_publish_commands(sftp, server, &upload, &server_file_dir.to_string_lossy().to_string(), &unzip_dir_str, log_func.clone()) {
+        let mut result = match Self::touch_publish_commands(session, sftp, server, &upload, &server_file_dir.to_string_lossy().to_string(), &unzip_dir_str, log_func.clone()) {
             Ok(result) => result,
             Err(err) => {
                 let msg = format!("publish {} error: {}", file_name, err);
                 error!("{}", &msg);
-                Self::end(sftp, session, &server_file_path, &unzip_dir_str, zip_file_path, true, log_func.clone());
+                Self::end(session, &server_temp_path_str, zip_file_path, true, upload.keep_temp, log_func.clone());
                 return Err(Error::convert_string(&msg));
             }
         };
+        timings.insert(String::from("compare"), start_time.elapsed());
 
         // 输出日志
         SftpHandler::log_info(&format!("exec commands:\n {:#?}", result.exec_commands), log_func.clone());
@@ -267,63 +665,143 @@ impl SftpUpload {
             delete_dir = need_delete_dir
         }
 
+        // dry run, 只输出命令不执行, 并保留远程临时目录用于检查
+        if upload.dry_run {
+            SftpHandler::log_info(&format!("dry run, skip exec commands, file count: {}, delete file count: {}", result.file_count, result.delete_file_count), log_func.clone());
+            result.dry_run = true;
+            Self::end(session, &server_temp_path_str, zip_file_path, delete_dir, true, log_func.clone());
+            return Ok(result);
+        }
+
         // 执行发布命令
         if result.exec_commands.is_empty() {
             // 输出日志
             SftpHandler::log_info("no commands need to exec !", log_func.clone());
-            Self::end(sftp, session, &server_file_path, &unzip_dir_str, zip_file_path, delete_dir, log_func.clone());
-            return Ok(result);
+        } else {
+            let start_time = Instant::now();
+            match Self::exec_command(session, result.exec_commands.clone(), log_func.clone()) {
+                Ok(_) => {
+                    timings.insert(String::from("exec"), start_time.elapsed());
+                    if !upload.keep_backup {
+                        result.backup_dir = None;
+                    }
+                }
+                Err(err) => {
+                    // 输出日志
+                    let msg = format!("publish {} error: {}", file_name, err);
+                    SftpHandler::log_error(&msg, log_func.clone());
+                    if let Some(backup_dir) = &result.backup_dir {
+                        Self::restore_backup(session, &server_file_dir.to_string_lossy(), backup_dir, log_func.clone());
+                    }
+                    Self::end(session, &server_temp_path_str, zip_file_path, true, upload.keep_temp, log_func.clone());
+                    return Err(Error::convert_string(&msg));
+                }
+            }
         }
 
-        match Self::exec_command(session, result.exec_commands.clone(), log_func.clone()) {
-            Ok(_) => {
-                Self::end(sftp, session, &server_file_path, &unzip_dir_str, zip_file_path, delete_dir, log_func.clone());
-            }
-            Err(err) => {
-                // 输出日志
-                let msg = format!("publish {} error: {}", file_name, err);
-                SftpHandler::log_error("no commands need to exec !", log_func.clone());
-                Self::end(sftp, session, &server_file_path, &unzip_dir_str, zip_file_path, true, log_func.clone());
-                return Err(Error::convert_string(&msg));
-            }
+        // 执行自定义命令, 如重启 nginx
+        if let Err(err) = Self::exec_custom_commands(session, upload, timings, log_func.clone()) {
+            let msg = format!("publish {} error: {}", file_name, err);
+            SftpHandler::log_error(&msg, log_func.clone());
+            Self::end(session, &server_temp_path_str, zip_file_path, true, upload.keep_temp, log_func.clone());
+            return Err(Error::convert_string(&msg));
         }
 
+        Self::end(session, &server_temp_path_str, zip_file_path, delete_dir, upload.keep_temp, log_func.clone());
         Ok(result)
     }
 
-    /// 结束
-    fn end<F>(sftp: &Sftp, session: &Session, server_file_path: &PathBuf, unzip_dir_str: &str, zip_file_path: &str, need_delete_dir: bool, log_func: Arc<Mutex<F>>)
+    /// 发布完成后按顺序执行 `upload.cmds`, 任一命令失败则中止
+    fn exec_custom_commands<F>(session: &Session, upload: &Upload, timings: &mut HashMap<String, Duration>, log_func: Arc<Mutex<F>>) -> Result<(), String>
     where
         F: FnMut(&str),
     {
-        SftpHandler::log_info(&format!("upload end, begin to delete local and server zip file: {:?} 、 unzip dir: {}", server_file_path, unzip_dir_str), log_func.clone());
+        let cmds: Vec<String> = upload.cmds.iter().map(|cmd| cmd.trim().to_string()).filter(|cmd| !cmd.is_empty()).collect();
+        if cmds.is_empty() {
+            return Ok(());
+        }
 
-        let _ = sftp.unlink(&server_file_path).map_err(|err| {
-            let msg = format!("delete file `{:?}` error: {:#?}", server_file_path, err);
-            SftpHandler::log_error(&msg, log_func.clone());
-            Error::convert_string(&msg)
-        });
+        SftpHandler::log_info(&format!("exec custom commands:\n {:#?}", cmds), log_func.clone());
+        let start_time = Instant::now();
+
+        let output = Self::exec_command(session, cmds, log_func.clone())?;
+        for line in output.lines() {
+            SftpHandler::log_info(line, log_func.clone());
+        }
 
-        let _ = Self::exec_command(session, vec![format!("rm -rf {}", unzip_dir_str)], log_func.clone());
+        timings.insert(String::from("cmds"), start_time.elapsed());
+        SftpHandler::log_info("exec custom commands success !", log_func.clone());
+        Ok(())
+    }
+
+    /// 结束
+    fn end<F>(session: &Session, server_temp_dir: &str, zip_file_path: &str, need_delete_dir: bool, keep_temp: bool, log_func: Arc<Mutex<F>>)
+    where
+        F: FnMut(&str),
+    {
+        if keep_temp {
+            SftpHandler::log_info(&format!("upload end, keep server temp dir: {}", server_temp_dir), log_func.clone());
+        } else {
+            // 临时目录只属于本次上传, 包含 zip 包和解压目录
+            SftpHandler::log_info(&format!("upload end, begin to delete local zip file and server temp dir: {}", server_temp_dir), log_func.clone());
+            let _ = Self::exec_command(session, vec![format!("rm -rf {}", server_temp_dir)], log_func.clone());
+        }
 
         // 删除本地压缩包
         if need_delete_dir {
             let _ = FileHandler::delete_file(zip_file_path);
-            SftpHandler::log_info(&format!("upload end, delete local and server zip file: {:?} 、 unzip dir: {} success !", server_file_path, unzip_dir_str), log_func.clone());
+            if keep_temp {
+                SftpHandler::log_info(&format!("upload end, delete local zip file: {} success !", zip_file_path), log_func.clone());
+            } else {
+                SftpHandler::log_info(&format!("upload end, delete local zip file and server temp dir: {} success !", server_temp_dir), log_func.clone());
+            }
         } else {
             SftpHandler::log_info("upload end !", log_func.clone());
         }
     }
 
+    /// 发布失败时使用备份恢复原来的文件目录
+    fn restore_backup<F>(session: &Session, file_dir: &str, backup_dir: &str, log_func: Arc<Mutex<F>>)
+    where
+        F: FnMut(&str),
+    {
+        SftpHandler::log_info(&format!("restore dir: {} from backup: {} ...", file_dir, backup_dir), log_func.clone());
+        let cmd = format!("if [ -d {} ]; then rm -rf {} && mv {} {}; fi", backup_dir, file_dir, backup_dir, file_dir);
+        match Self::exec_command(session, vec![cmd], log_func.clone()) {
+            Ok(_) => SftpHandler::log_info(&format!("restore dir: {} success !", file_dir), log_func.clone()),
+            Err(err) => SftpHandler::log_error(&format!("restore dir: {} from backup: {} error: {}", file_dir, backup_dir, err), log_func.clone()),
+        }
+    }
+
+    /// 获取本次上传的远程临时目录, 默认为 `server_dir` 父目录下的 `__SFTP_TEMP_DIR__`, 可通过 `server_temp_dir` 指定
+    /// 每次上传使用单独的子目录, 防止同时发布时互相覆盖
+    fn get_server_temp_dir(upload: &Upload) -> String {
+        let server_temp_dir = match upload.server_temp_dir.as_deref().map(|dir| dir.trim()).filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => {
+                let server_dir = PathBuf::from(upload.server_dir.trim());
+                match server_dir.parent() {
+                    Some(parent) => parent.join(UPLOAD_TEMP_DIR),
+                    None => server_dir.join(UPLOAD_TEMP_DIR),
+                }
+            }
+        };
+
+        return server_temp_dir.join(Uuid::new_v4().to_string()).to_string_lossy().to_string();
+    }
+
     /// 重命令上传目录，添加时间戳
-    fn rename_file_upload_path(zip_file_path: &str) -> Result<String, String> {
+    fn rename_file_upload_path(zip_file_path: &str, compression: CompressionFormat) -> Result<String, String> {
         // 获取临时文件名
         let data_suffix: String = chrono::Local::now().format("%Y%m%d%H%M%S").to_string(); // 生成时间后缀
 
+        // `.tar.gz` 包含两个后缀, 不能使用 file_stem
+        let extension = format!(".{}", compression.extension());
         let mut temp_file_path = PathBuf::from(&zip_file_path);
-        let temp_file_name = temp_file_path.file_stem().unwrap().to_str().unwrap_or("").to_string() + "-" + data_suffix.as_str();
+        let file_name = temp_file_path.file_name().unwrap_or(OsStr::new("")).to_string_lossy().to_string();
+        let file_stem = file_name.strip_suffix(&extension).unwrap_or(&file_name);
+        let temp_file_name = format!("{}-{}{}", file_stem, data_suffix, extension);
         temp_file_path.set_file_name(&temp_file_name);
-        temp_file_path.set_extension("zip");
 
         let temp_file_str = temp_file_path.to_string_lossy().to_string();
         info!("get upload temp filename: {}", temp_file_name);
@@ -333,8 +811,31 @@ impl SftpUpload {
         Ok(temp_file_str)
     }
 
+    /// 远程解压 zip 包, 失败时等待后重试, 上传的文件可能还未刷新(如 NFS), 最多尝试 `upload.unzip_retries` 次
+    fn uncompress_zip_with_retry<F>(session: &Session, sftp: &Sftp, upload: &Upload, upload_temp_dir: &str, zip_file_name: &str, unzip_dir_str: &str, log_func: Arc<Mutex<F>>) -> Result<(), String>
+    where
+        F: FnMut(&str),
+    {
+        let retries = upload.unzip_retries.unwrap_or(DEFAULT_UNZIP_RETRIES).max(1);
+        let mut attempt = 1;
+        loop {
+            match Self::uncompress_zip(session, sftp, upload_temp_dir, zip_file_name, unzip_dir_str, upload.compression, log_func.clone()) {
+                Ok(_) => return Ok(()),
+                Err(err) => {
+                    if attempt >= retries {
+                        return Err(err);
+                    }
+
+                    SftpHandler::log_error(&format!("uncompress zip failed ({}/{}), retry after {:?}: {}", attempt, retries, UNZIP_RETRY_DELAY, err), log_func.clone());
+                    std::thread::sleep(UNZIP_RETRY_DELAY);
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
     /// 远程解压 zip 包
-    fn uncompress_zip<F>(session: &Session, sftp: &Sftp, upload_temp_dir: &str, zip_file_name: &str, unzip_dir_str: &str, log_func: Arc<Mutex<F>>) -> Result<(), String>
+    fn uncompress_zip<F>(session: &Session, sftp: &Sftp, upload_temp_dir: &str, zip_file_name: &str, unzip_dir_str: &str, compression: CompressionFormat, log_func: Arc<Mutex<F>>) -> Result<(), String>
     where
         F: FnMut(&str),
     {
@@ -350,7 +851,10 @@ impl SftpUpload {
         let commands: Vec<String> = vec![
             format!("cd {}", upload_temp_dir),
             format!("rm -rf {}", unzip_dir_str),
-            format!("unzip {} -d {}", &zip_file_path_str, upload_temp_dir), // unzip 到指定目录 -o 为强制覆盖
+            match compression {
+                CompressionFormat::Zip => format!("unzip {} -d {}", &zip_file_path_str, upload_temp_dir), // unzip 到指定目录 -o 为强制覆盖
+                CompressionFormat::TarGz => format!("tar -xzf {} -C {}", &zip_file_path_str, upload_temp_dir),
+            },
         ];
 
         SftpHandler::log_info(&format!("unzip: {} ... ", zip_file_path_str), log_func.clone());
@@ -362,7 +866,7 @@ impl SftpUpload {
     /// 发布
     /// 判断是不是增量发布
     /// 非增量发布, 需要比较文件夹内的文件是否一致, 如果不一致则替换
-    fn touch_publish_commands<F>(sftp: &Sftp, server: &Server, upload: &Upload, file_dir: &str, temp_file_dir: &str, log_func: Arc<Mutex<F>>) -> Result<SftpUploadResult, String>
+    fn touch_publish_commands<F>(session: &Session, sftp: &Sftp, server: &Server, upload: &Upload, file_dir: &str, temp_file_dir: &str, log_func: Arc<Mutex<F>>) -> Result<SftpUploadResult, String>
     where
         F: FnMut(&str),
     {
@@ -389,14 +893,25 @@ impl SftpUpload {
             return Err(Error::convert_string(&msg));
         }
 
-        // 全量发布
-        let get_full_publish_cmds = || {
+        // 全量发布, 先备份原来的文件目录, 新目录移动成功后再删除备份
+        let get_full_publish_cmds = |has_dir: bool| {
             SftpHandler::log_info(&format!("use full publish, file count: {} ...", temp_files.len()), log_func.clone());
             let mut result = SftpUploadResult::default();
 
             let mut cmds: Vec<String> = Vec::new();
-            cmds.push(format!("rm -rf {}", file_dir)); // 删除原来的文件目录
+            if has_dir {
+                let backup_dir = format!("{}.bak-{}", file_dir, chrono::Local::now().format("%Y%m%d%H%M%S"));
+                cmds.push(format!("mv {} {}", file_dir, &backup_dir)); // 备份原来的文件目录
+                result.backup_dir = Some(backup_dir);
+            }
+
             cmds.push(format!("mv {} {}", temp_file_dir, &upload.server_dir)); // 移动临时目录到原来的文件目录
+            if let Some(backup_dir) = &result.backup_dir {
+                cmds.push(format!("test -d {}", file_dir)); // 确认新目录存在
+                if !upload.keep_backup {
+                    cmds.push(format!("rm -rf {}", backup_dir)); // 删除备份
+                }
+            }
 
             result.host = server.host.clone();
             result.file_count = temp_files.len() as u64;
@@ -408,23 +923,24 @@ impl SftpUpload {
 
         // 1. 目标目录不存在, 则直接采用全量发布(全量)
         if !sftp.stat(file_file_path).is_ok() {
-            return Ok(get_full_publish_cmds());
+            return Ok(get_full_publish_cmds(false));
         }
 
         // 2. 当 need_increment 为 false 时, 使用全量发布(全量)
         if !upload.need_increment {
             SftpHandler::log_info(&format!("upload field `need_increment` is false, full publish, file count: {} ...", temp_files.len()), log_func.clone());
-            return Ok(get_full_publish_cmds());
+            return Ok(get_full_publish_cmds(true));
         }
 
-        // 读取目录文件列表
+        // 读取目录文件列表, 排除的文件不参与比较, 也不会被删除
         let mut files: Vec<String> = Vec::new();
         Self::read_files(sftp, file_dir, &mut files);
+        let files = FileFilter::new(upload)?.filter_files(files, &[file_file_path]);
 
         // 3. 没有文件, 则取全量发布(全量)
         if files.len() == 0 {
             SftpHandler::log_info(&format!("read no files, full publish, file count: {} ...", temp_files.len()), log_func.clone());
-            return Ok(get_full_publish_cmds());
+            return Ok(get_full_publish_cmds(true));
         }
 
         // 4. 当 need_increment 为 true 时, 使用增量发布(增量)
@@ -434,14 +950,28 @@ impl SftpUpload {
         result.host = server.host.clone();
 
         // 用临时目录和比较原来目录进行比较, 获取不同的文件
-        let differences = Self::get_compare_file(sftp, &files, &temp_files, file_dir, temp_file_dir, log_func.clone());
+        let hashes = Self::get_remote_hashes(session, &[file_dir, temp_file_dir], log_func.clone());
+        let differences = Self::get_compare_file(
+            &FileHashSource {
+                sftp: Some(sftp),
+                hashes: &hashes,
+                is_local: false,
+            },
+            &files,
+            &temp_files,
+            file_dir,
+            temp_file_dir,
+            log_func.clone(),
+        );
         SftpHandler::log_info(&format!("difference file count: {}", differences.len()), log_func.clone());
 
         result.file_count = differences.len() as u64; // 设置发布文件个数
         result.file_list = differences.clone().iter().map(|d| d.relative_path.clone()).collect();
+        result.changed_files = result.file_list.clone();
 
-        let remove_cmds = Self::remove_no_used_files_in_dir(&files, &temp_files, file_dir, temp_file_dir, log_func.clone());
+        let (remove_cmds, deleted_files) = Self::remove_no_used_files_in_dir(&files, &temp_files, file_dir, temp_file_dir, log_func.clone());
         result.delete_file_count = remove_cmds.len() as u64; // 设置删除文件个数
+        result.deleted_files = deleted_files;
         SftpHandler::log_info(&format!("remove cmds: \n {:#?}", remove_cmds), log_func.clone());
 
         if differences.is_empty() {
@@ -449,7 +979,7 @@ impl SftpUpload {
             // 移除没用的文件
             commands.extend(remove_cmds);
         } else {
-            let cmds = Self::get_increment_files_commands(sftp, &differences, file_dir, log_func.clone());
+            let cmds = Self::get_increment_files_commands(sftp, &differences, file_dir, upload.preserve_mtime, log_func.clone());
             SftpHandler::log_info(&format!("increment cmds: \n {:#?}", cmds), log_func.clone());
             commands.extend(cmds);
             commands.extend(remove_cmds)
@@ -460,8 +990,28 @@ impl SftpUpload {
         Ok(result)
     }
 
+    /// 批量获取远程目录下文件的 hash, 某个目录失败时(如没有 `sha256sum`)保留已获取的 hash, 缺少的文件比较时逐个获取
+    fn get_remote_hashes<F>(session: &Session, dirs: &[&str], log_func: Arc<Mutex<F>>) -> HashMap<String, String>
+    where
+        F: FnMut(&str),
+    {
+        let mut hashes: HashMap<String, String> = HashMap::new();
+        for dir in dirs {
+            match SftpHandler::get_remote_hashes(session, dir) {
+                Ok(dir_hashes) => hashes.extend(dir_hashes),
+                Err(err) => {
+                    SftpHandler::log_error(&format!("get remote hashes in `{}` error, fallback to get file hash one by one: {}", dir, err), log_func.clone());
+                }
+            }
+        }
+
+        SftpHandler::log_info(&format!("get remote hashes success, file count: {}", hashes.len()), log_func.clone());
+        return hashes;
+    }
+
     /// 获取两个目录的比较文件, 此处使用并行任务并没有快多少
-    fn get_compare_file<F>(sftp: &Sftp, files: &Vec<String>, temp_files: &Vec<String>, file_dir: &str, temp_file_dir: &str, log_func: Arc<Mutex<F>>) -> Vec<SftpUploadDifferent>
+    /// hash_source: 获取文件 hash 的方式, 见 `FileHashSource`
+    fn get_compare_file<F>(hash_source: &FileHashSource, files: &Vec<String>, temp_files: &Vec<String>, file_dir: &str, temp_file_dir: &str, log_func: Arc<Mutex<F>>) -> Vec<SftpUploadDifferent>
     where
         F: FnMut(&str),
     {
@@ -487,7 +1037,7 @@ impl SftpUpload {
                 if has_same {
                     if &file_name == &temp_file_name {
                         SftpHandler::log_info(&format!("filename {} same, compare file hash !", &file_name), log_func.clone());
-                        if !Self::compare_two_file_hash(sftp, find_file, temp_file, log_func.clone()) {
+                        if !Self::compare_two_file_hash(hash_source, find_file, temp_file, log_func.clone()) {
                             differences.push(SftpUploadDifferent {
                                 temp_path: temp_file.to_string(),
                                 old_path: find_file.to_string(),
@@ -505,7 +1055,7 @@ impl SftpUpload {
                     // 不带 hash, 判断文件名和 hash 是否一致
                     if &file_name == &temp_file_name {
                         SftpHandler::log_info(&format!("filename {} same but it has no hash code, compare file hash !", &file_name), log_func.clone());
-                        if !Self::compare_two_file_hash(sftp, find_file, temp_file, log_func.clone()) {
+                        if !Self::compare_two_file_hash(hash_source, find_file, temp_file, log_func.clone()) {
                             differences.push(SftpUploadDifferent {
                                 temp_path: temp_file.to_string(),
                                 old_path: find_file.to_string(),
@@ -571,11 +1121,23 @@ impl SftpUpload {
     }
 
     /// 比较两个文件的 hash 值是否一致
-    fn compare_two_file_hash<F>(sftp: &Sftp, file: &str, temp_file: &str, log_func: Arc<Mutex<F>>) -> bool
+    fn compare_two_file_hash<F>(hash_source: &FileHashSource, file: &str, temp_file: &str, log_func: Arc<Mutex<F>>) -> bool
     where
         F: FnMut(&str),
     {
-        let file_hash = match SftpHandler::get_file_hash(sftp, file) {
+        let FileHashSource { sftp, hashes, is_local } = *hash_source;
+        let get_remote_hash = |sftp: &Sftp, file_path: &str| match hashes.get(&SftpHandler::normalize_path(file_path)) {
+            Some(hash) => Ok(hash.clone()),
+            None => SftpHandler::get_file_hash(sftp, file_path),
+        };
+
+        // 没有 sftp 时, 两个文件都在本地
+        let file_hash = match sftp {
+            Some(sftp) => get_remote_hash(sftp, file),
+            None => FileHandler::get_file_hash(file),
+        };
+
+        let file_hash = match file_hash {
             Ok(hash) => hash,
             Err(err) => {
                 SftpHandler::log_error(&format!("get file `{}` hash error: {}", file, err), log_func.clone());
@@ -587,7 +1149,11 @@ impl SftpUpload {
             return false;
         }
 
-        let temp_file_hash = match SftpHandler::get_file_hash(sftp, temp_file) {
+        let temp_file_hash = match sftp {
+            Some(sftp) if !is_local => get_remote_hash(sftp, temp_file),
+            _ => FileHandler::get_file_hash(temp_file),
+        };
+        let temp_file_hash = match temp_file_hash {
             Ok(hash) => hash,
             Err(err) => {
                 SftpHandler::log_error(&format!("get file `{}` hash error: {}", temp_file, err), log_func.clone());
@@ -638,39 +1204,44 @@ impl SftpUpload {
     }
 
     /// 移除不用的文件
-    fn remove_no_used_files_in_dir<F>(files: &Vec<String>, temp_files: &Vec<String>, file_dir: &str, temp_file_dir: &str, log_func: Arc<Mutex<F>>) -> Vec<String>
+    fn remove_no_used_files_in_dir<F>(files: &Vec<String>, temp_files: &Vec<String>, file_dir: &str, temp_file_dir: &str, log_func: Arc<Mutex<F>>) -> (Vec<String>, Vec<String>)
     where
         F: FnMut(&str),
     {
-        let commands: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
-
-        files.iter().for_each(|file| {
-            // 比较文件
-            let file_relative_path = Path::new(&file).strip_prefix(file_dir).unwrap_or(&Path::new("")).to_string_lossy().to_string();
-            let find_file = temp_files.par_iter().find_first(|f| {
-                let temp_file_relative_path = Path::new(f).strip_prefix(temp_file_dir).unwrap_or(&Path::new("")).to_string_lossy().to_string();
-                return &file_relative_path == &temp_file_relative_path;
-            });
-
-            // 未找到文件, 则需要删除
-            if find_file.is_none() {
-                SftpHandler::log_info(&format!("file {} is not used, will be deleted !", file), log_func.clone());
-                let mut commands = commands.lock().unwrap();
-                commands.push(format!("rm -rf {}", file));
-            }
-        });
+        let mut commands: Vec<String> = Vec::new();
+        let mut deleted_files: Vec<String> = Vec::new();
+        for file in Self::get_no_used_files(files, temp_files, file_dir, temp_file_dir).iter() {
+            SftpHandler::log_info(&format!("file {} is not used, will be deleted !", file), log_func.clone());
+            commands.push(format!("rm -rf {}", file));
+            deleted_files.push(Path::new(file).strip_prefix(file_dir).unwrap_or(Path::new(file)).to_string_lossy().to_string());
+        }
 
-        let commands = commands.lock().unwrap();
-        let commands = commands.clone();
         if commands.is_empty() {
             SftpHandler::log_info("no used file to be delete !", log_func.clone());
         }
 
-        return commands;
+        return (commands, deleted_files);
+    }
+
+    /// 获取新目录中不存在的旧文件(按相对路径比较)
+    fn get_no_used_files(files: &Vec<String>, temp_files: &Vec<String>, file_dir: &str, temp_file_dir: &str) -> Vec<String> {
+        files
+            .iter()
+            .filter(|file| {
+                let file_relative_path = Path::new(&file).strip_prefix(file_dir).unwrap_or(&Path::new("")).to_string_lossy().to_string();
+                let find_file = temp_files.par_iter().find_first(|f| {
+                    let temp_file_relative_path = Path::new(f).strip_prefix(temp_file_dir).unwrap_or(&Path::new("")).to_string_lossy().to_string();
+                    return &file_relative_path == &temp_file_relative_path;
+                });
+
+                find_file.is_none()
+            })
+            .cloned()
+            .collect()
     }
 
     /// 获取增量发布的命令
-    fn get_increment_files_commands<F>(sftp: &Sftp, differences: &Vec<SftpUploadDifferent>, file_dir: &str, log_func: Arc<Mutex<F>>) -> Vec<String>
+    fn get_increment_files_commands<F>(sftp: &Sftp, differences: &Vec<SftpUploadDifferent>, file_dir: &str, preserve_mtime: bool, log_func: Arc<Mutex<F>>) -> Vec<String>
     where
         F: FnMut(&str),
     {
@@ -685,7 +1256,8 @@ impl SftpUpload {
                 if !d.relative_path.is_empty() {
                     let file_path = Path::new(file_dir).join(&d.relative_path);
                     commands.push(format!("rm -rf {}", d.old_path)); // 1. 删除旧的文件
-                    commands.push(format!("cp {} {}", d.temp_path, &file_path.as_path().to_string_lossy().to_string()));
+                    let cp = if preserve_mtime { "cp -p" } else { "cp" }; // -p 保留文件时间
+                    commands.push(format!("{} {} {}", cp, d.temp_path, &file_path.as_path().to_string_lossy().to_string()));
                 }
             } else {
                 SftpHandler::log_info(&format!("file path {} not exists !", &d.temp_path), log_func.clone())
@@ -715,30 +1287,39 @@ impl SftpUpload {
         }
     }
 
-    fn exec_command<F>(session: &Session, cmds: Vec<String>, log_func: Arc<Mutex<F>>) -> Result<(), String>
+    fn exec_command<F>(session: &Session, cmds: Vec<String>, log_func: Arc<Mutex<F>>) -> Result<String, String>
     where
         F: FnMut(&str),
     {
         SftpHandler::log_info("exec command ...", log_func.clone());
         let mut channel = SftpHandler::create_channel(session)?;
-        let command = cmds.join(" \n ");
+        // 任一命令失败则退出, 不再执行后面的命令, 退出码即为失败命令的退出码
+        let command = format!("set -e \n {}", cmds.join(" \n "));
         SftpHandler::log_info(&format!("exec server command:\n {}", command), log_func.clone());
 
         channel.exec(&command).map_err(|err| Error::Error(err.to_string()).to_string())?;
         let (content, error) = SftpHandler::get_channel_output(&mut channel)?;
-        if !error.is_empty() {
-            let msg = format!("exec server commands error: {}", &error);
+        SftpHandler::close_channel(&mut channel)?;
+
+        // 以退出码判断是否成功, stderr 只作为诊断输出
+        let exit_status = SftpHandler::get_channel_exit_status(&mut channel)?;
+        if exit_status != 0 {
+            let output = if error.trim().is_empty() { &content } else { &error };
+            let msg = format!("exec server commands error, exit code: {}, output: {}", exit_status, output);
             info!("{}", msg);
             return Err(Error::convert_string(&msg));
         }
 
+        if !error.trim().is_empty() {
+            SftpHandler::log_info(&format!("exec server commands stderr: {}", &error), log_func.clone());
+        }
+
         // 打印日志
         SftpHandler::log_info("command output: \n", log_func.clone());
         for line in content.lines() {
             info!("{}", line);
         }
 
-        SftpHandler::close_channel(&mut channel)?;
-        Ok(())
+        Ok(content)
     }
 }
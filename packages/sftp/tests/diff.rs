@@ -0,0 +1,40 @@
+//! 测试本地目录比较
+
+use sftp::upload::SftpUpload;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn create_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("sftp-diff-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    for (file, content) in files {
+        let file_path = dir.join(file);
+        fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+        fs::write(file_path, content).unwrap();
+    }
+
+    dir
+}
+
+fn to_str(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+#[test]
+fn test_diff_local_dirs() {
+    let old_dir = create_dir("old", &[("index.html", "<html>old</html>"), ("static/app.1a2b3c.js", "old js"), ("static/logo.png", "logo"), ("removed.txt", "removed")]);
+    let new_dir = create_dir("new", &[("index.html", "<html>new</html>"), ("static/app.4d5e6f.js", "new js"), ("static/logo.png", "logo"), ("added.txt", "added")]);
+
+    let diff = SftpUpload::diff_local_dirs(&to_str(&old_dir), &to_str(&new_dir), |_| {}).unwrap();
+    assert_eq!(diff.added, vec!["added.txt".to_string()]);
+    assert_eq!(diff.changed, vec!["index.html".to_string(), "static/app.4d5e6f.js".to_string()]);
+    assert_eq!(diff.removed, vec!["removed.txt".to_string(), "static/app.1a2b3c.js".to_string()]);
+
+    fs::remove_dir_all(old_dir).unwrap();
+    fs::remove_dir_all(new_dir).unwrap();
+}
+
+#[test]
+fn test_diff_local_dirs_not_exists() {
+    assert!(SftpUpload::diff_local_dirs("/path/not/exists/old", "/path/not/exists/new", |_| {}).is_err());
+}
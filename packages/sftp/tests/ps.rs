@@ -0,0 +1,54 @@
+//! 测试 ps 输出解析
+
+use sftp::runnable::SftpRunnableHandler;
+
+const LINUX_PS_AUX: &str = "USER         PID %CPU %MEM    VSZ   RSS TTY      STAT START   TIME COMMAND
+root           1  0.0  0.1 167744 11200 ?        Ss   Jan01   0:09 /sbin/init
+root         812  0.0  0.0   6608  2200 pts/0    S+   10:05   0:00 grep n-nacos-tools
+root        2345  0.3  1.2 812340 98765 ?        Sl   10:02   1:23 /usr/local/test/__MONITOR__/n-nacos-tools";
+
+const MACOS_PS_AUX: &str = "USER               PID  %CPU %MEM      VSZ    RSS   TT  STAT STARTED      TIME COMMAND
+smile              401   0.0  0.2 408652304  30480   ??  S     9:58AM   0:01.20 /usr/libexec/trustd
+smile            51234   0.0  0.1 408638528  12345   ??  S    10:02AM   0:00.12 /usr/local/test/n-nacos-tools";
+
+const BUSYBOX_PS: &str = "PID   USER     TIME  COMMAND
+    1 root      0:00 /sbin/init
+   23 root      0:01 /usr/local/test/n-nacos-tools
+   31 root      0:00 ps";
+
+#[test]
+fn test_parse_ps_linux() {
+    let pid = SftpRunnableHandler::parse_ps_pid(LINUX_PS_AUX, "n-nacos-tools", None);
+    assert_eq!(pid, Some("2345".to_string()));
+}
+
+#[test]
+fn test_parse_ps_macos() {
+    let pid = SftpRunnableHandler::parse_ps_pid(MACOS_PS_AUX, "n-nacos-tools", None);
+    assert_eq!(pid, Some("51234".to_string()));
+}
+
+#[test]
+fn test_parse_ps_busybox() {
+    let pid = SftpRunnableHandler::parse_ps_pid(BUSYBOX_PS, "n-nacos-tools", None);
+    assert_eq!(pid, Some("23".to_string()));
+}
+
+#[test]
+fn test_parse_ps_pid_column() {
+    let output = "1 root 0:00 /sbin/init\n23 root 0:01 /usr/local/test/n-nacos-tools";
+    assert_eq!(SftpRunnableHandler::parse_ps_pid(output, "n-nacos-tools", Some(0)), Some("23".to_string()));
+    assert_eq!(SftpRunnableHandler::parse_ps_pid(output, "not-running", Some(0)), None);
+}
+
+#[test]
+fn test_parse_ps_pids_full_path() {
+    let output = "PID   USER     TIME  COMMAND
+   23 root      0:01 /usr/local/test/server
+   24 root      0:01 /usr/local/other/server
+   25 root      0:01 /usr/local/test/server --port 8080
+   26 root      0:00 tail -f /usr/local/test/server.log";
+    assert_eq!(SftpRunnableHandler::parse_ps_pids(output, "/usr/local/test/server", None), vec!["23".to_string(), "25".to_string()]);
+    assert_eq!(SftpRunnableHandler::parse_ps_pids(output, "server", None), vec!["23".to_string(), "24".to_string(), "25".to_string()]);
+    assert!(SftpRunnableHandler::parse_ps_pids(output, "/usr/local/test/serv", None).is_empty());
+}